@@ -0,0 +1,271 @@
+// bech32m text encoding of addresses, see BIP-350
+use crate::{circuit::IVC, Address, FWrap};
+use ark_ff::PrimeField;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const SEPARATOR: char = '1';
+const CHECKSUM_LEN: usize = 6;
+const BECH32M_CONST: u32 = 0x2bc8_30a3;
+const MAX_LEN: usize = 90;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressParseError {
+    // no `1` separating the human readable part and the data
+    MissingSeparator,
+    // human readable part is empty or has characters out of range
+    InvalidHrp,
+    // human readable part is valid but not the expected one
+    HrpMismatch { expected: String, found: String },
+    // mix of upper and lower case characters
+    MixedCase,
+    // encoded string is too short or too long
+    InvalidLength,
+    // character at the position is not in the bech32 charset
+    InvalidChar { position: usize, char: char },
+    // checksum does not match. when a single character substitution fixes
+    // the checksum `position` points to that character
+    InvalidChecksum { position: Option<usize> },
+    // data part does not convert back to whole bytes
+    InvalidPadding,
+    // data is not a canonical field element
+    InvalidFieldElement,
+}
+
+impl fmt::Display for AddressParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingSeparator => write!(f, "missing separator '{}'", SEPARATOR),
+            Self::InvalidHrp => write!(f, "invalid human readable part"),
+            Self::HrpMismatch { expected, found } => {
+                write!(f, "expected prefix '{}', found '{}'", expected, found)
+            }
+            Self::MixedCase => write!(f, "mixed case"),
+            Self::InvalidLength => write!(f, "invalid length"),
+            Self::InvalidChar { position, char } => {
+                write!(f, "invalid character '{}' at position {}", char, position)
+            }
+            Self::InvalidChecksum {
+                position: Some(position),
+            } => write!(f, "invalid checksum, likely typo at position {}", position),
+            Self::InvalidChecksum { position: None } => write!(f, "invalid checksum"),
+            Self::InvalidPadding => write!(f, "invalid padding"),
+            Self::InvalidFieldElement => write!(f, "not a field element"),
+        }
+    }
+}
+
+impl ark_std::error::Error for AddressParseError {}
+
+fn polymod(values: impl Iterator<Item = u8>) -> u32 {
+    const GEN: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    values.fold(1u32, |chk, v| {
+        let top = chk >> 25;
+        let chk = ((chk & 0x1ff_ffff) << 5) ^ v as u32;
+        GEN.iter()
+            .enumerate()
+            .filter(|(i, _)| (top >> i) & 1 == 1)
+            .fold(chk, |chk, (_, g)| chk ^ g)
+    })
+}
+
+fn hrp_expand(hrp: &str) -> impl Iterator<Item = u8> + '_ {
+    hrp.bytes()
+        .map(|b| b >> 5)
+        .chain(std::iter::once(0))
+        .chain(hrp.bytes().map(|b| b & 0x1f))
+}
+
+fn verify_checksum(hrp: &str, data: &[u8]) -> bool {
+    polymod(hrp_expand(hrp).chain(data.iter().copied())) == BECH32M_CONST
+}
+
+fn create_checksum(hrp: &str, data: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let values = hrp_expand(hrp)
+        .chain(data.iter().copied())
+        .chain([0u8; CHECKSUM_LEN]);
+    let pm = polymod(values) ^ BECH32M_CONST;
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    checksum
+        .iter_mut()
+        .enumerate()
+        .for_each(|(i, c)| *c = ((pm >> (5 * (5 - i))) & 0x1f) as u8);
+    checksum
+}
+
+// find the single character substitution that fixes the checksum
+fn locate_error(hrp: &str, data: &[u8]) -> Option<usize> {
+    let mut candidates = (0..data.len()).filter(|&i| {
+        let original = data[i];
+        let mut data = data.to_vec();
+        (0..32u8).filter(|v| *v != original).any(|v| {
+            data[i] = v;
+            verify_checksum(hrp, &data)
+        })
+    });
+    let position = candidates.next()?;
+    candidates.next().is_none().then_some(position)
+}
+
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc = 0u32;
+    let mut bits = 0u32;
+    let max = (1u32 << to) - 1;
+    let mut out = vec![];
+    for value in data {
+        acc = (acc << from) | *value as u32;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            out.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || ((acc << (to - bits)) & max) != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+fn valid_hrp(hrp: &str) -> bool {
+    !hrp.is_empty() && hrp.len() <= 83 && hrp.bytes().all(|b| (33..=126).contains(&b))
+}
+
+fn encode(hrp: &str, bytes: &[u8]) -> Result<String, AddressParseError> {
+    if !valid_hrp(hrp) {
+        return Err(AddressParseError::InvalidHrp);
+    }
+    let hrp = hrp.to_ascii_lowercase();
+    let hrp = hrp.as_str();
+    let data = convert_bits(bytes, 8, 5, true).unwrap();
+    if hrp.len() + 1 + data.len() + CHECKSUM_LEN > MAX_LEN {
+        return Err(AddressParseError::InvalidLength);
+    }
+    let checksum = create_checksum(hrp, &data);
+    let mut out = String::with_capacity(hrp.len() + 1 + data.len() + CHECKSUM_LEN);
+    out.push_str(hrp);
+    out.push(SEPARATOR);
+    data.iter()
+        .chain(checksum.iter())
+        .for_each(|v| out.push(CHARSET[*v as usize] as char));
+    Ok(out)
+}
+
+fn decode(s: &str) -> Result<(String, Vec<u8>), AddressParseError> {
+    if s.len() > MAX_LEN {
+        return Err(AddressParseError::InvalidLength);
+    }
+    let has_lower = s.bytes().any(|b| b.is_ascii_lowercase());
+    let has_upper = s.bytes().any(|b| b.is_ascii_uppercase());
+    if has_lower && has_upper {
+        return Err(AddressParseError::MixedCase);
+    }
+    let s = s.to_ascii_lowercase();
+
+    let sep = s
+        .rfind(SEPARATOR)
+        .ok_or(AddressParseError::MissingSeparator)?;
+    let (hrp, data) = (&s[..sep], &s[sep + 1..]);
+    if !valid_hrp(hrp) {
+        return Err(AddressParseError::InvalidHrp);
+    }
+    if data.len() < CHECKSUM_LEN {
+        return Err(AddressParseError::InvalidLength);
+    }
+
+    let data = data
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            CHARSET
+                .iter()
+                .position(|v| *v as char == c)
+                .map(|v| v as u8)
+                .ok_or(AddressParseError::InvalidChar {
+                    position: sep + 1 + i,
+                    char: c,
+                })
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    if !verify_checksum(hrp, &data) {
+        let position = locate_error(hrp, &data).map(|i| sep + 1 + i);
+        return Err(AddressParseError::InvalidChecksum { position });
+    }
+
+    let data = &data[..data.len() - CHECKSUM_LEN];
+    let bytes = convert_bits(data, 5, 8, false).ok_or(AddressParseError::InvalidPadding)?;
+    Ok((hrp.to_string(), bytes))
+}
+
+// human readable part naming the curve, `IVC::CURVE_ID` in lower case
+pub fn hrp<E: IVC>() -> String {
+    E::CURVE_ID.to_ascii_lowercase()
+}
+
+impl<F: PrimeField> Address<F> {
+    // encode with the human readable part naming the network or the curve
+    pub fn to_bech32m(&self, hrp: &str) -> Result<String, AddressParseError> {
+        encode(hrp, &self.to_bytes())
+    }
+
+    pub fn from_bech32m(s: &str, hrp: &str) -> Result<Self, AddressParseError> {
+        let (found, bytes) = decode(s)?;
+        let expected = hrp.to_ascii_lowercase();
+        if found != expected {
+            return Err(AddressParseError::HrpMismatch { expected, found });
+        }
+        let address =
+            Self::from_bytes(&bytes).map_err(|_err| AddressParseError::InvalidFieldElement)?;
+        // reject trailing or non canonical bytes
+        (address.to_bytes() == bytes)
+            .then_some(address)
+            .ok_or(AddressParseError::InvalidFieldElement)
+    }
+}
+
+// text form of an address under the prefix of the config `E`
+pub struct Bech32m<E: IVC>(pub Address<E::Field>, PhantomData<E>);
+
+impl<E: IVC> Bech32m<E> {
+    pub fn new(address: Address<E::Field>) -> Self {
+        Self(address, PhantomData)
+    }
+
+    pub fn address(&self) -> &Address<E::Field> {
+        &self.0
+    }
+}
+
+impl<E: IVC> From<Address<E::Field>> for Bech32m<E> {
+    fn from(address: Address<E::Field>) -> Self {
+        Self::new(address)
+    }
+}
+
+impl<E: IVC> fmt::Display for Bech32m<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // only a curve id unfit for a prefix fails here
+        let s = self.0.to_bech32m(&hrp::<E>()).map_err(|_| fmt::Error)?;
+        write!(f, "{}", s)
+    }
+}
+
+impl<E: IVC> FromStr for Bech32m<E> {
+    type Err = AddressParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Address::from_bech32m(s, &hrp::<E>()).map(Self::new)
+    }
+}
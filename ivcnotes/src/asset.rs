@@ -1,5 +1,12 @@
-use crate::{Address, AssetHash, FWrap};
+use crate::{
+    deserialize_version, poseidon::PoseidonConfigs, serialize_version, Address, AssetHash, FWrap,
+};
+use ark_crypto_primitives::sponge::Absorb;
 use ark_ff::PrimeField;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
+};
 use digest::Digest;
 
 #[derive(Debug, Clone, Copy)]
@@ -16,33 +23,492 @@ impl<F: PrimeField> Asset<F> {
         }
     }
 
-    pub(crate) fn hash(&self) -> AssetHash<F> {
-        let bytes = sha2::Sha512::new()
-            .chain_update(self.terms.to_bytes())
-            .chain_update(self.issuer.to_bytes())
-            .finalize();
-        AssetHash::reduce_bytes(bytes.as_ref())
+    pub fn issuer(&self) -> &Address<F> {
+        &self.issuer
+    }
+
+    pub fn terms(&self) -> &Terms {
+        &self.terms
+    }
+
+    // commitment to the issuer and terms, see `PoseidonConfigs::asset`
+    pub fn hash(&self, h: &PoseidonConfigs<F>) -> AssetHash<F>
+    where
+        F: Absorb,
+    {
+        h.asset(self)
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+// every kind may cap the total value its issuer can ever issue
 pub enum Terms {
-    IOU { maturity: u64, unit: u64 },
+    IOU {
+        maturity: u64,
+        unit: u64,
+        cap: Option<u64>,
+    },
+    // interest bearing note. a coupon of `coupon` basis points of the face value is
+    // due every `period` seconds from `start` up to the maturity
+    Bond {
+        start: u64,
+        maturity: u64,
+        period: u64,
+        coupon: u32,
+        unit: u64,
+        cap: Option<u64>,
+    },
+    // prepaid note that can only be spent at a single merchant until expiry
+    Voucher {
+        expiry: u64,
+        merchant: [u8; 32],
+        unit: u64,
+        cap: Option<u64>,
+    },
+    // plain fungible token, redeemable at any time and never expires
+    Token {
+        unit: u64,
+        cap: Option<u64>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TermsKind {
+    IOU,
+    Bond,
+    Voucher,
+    Token,
 }
 
+// basis points in one
+pub const BASIS_POINTS: u64 = 10_000;
+
 impl Terms {
     pub fn iou(maturity: u64, unit: u64) -> Self {
-        Terms::IOU { maturity, unit }
+        Terms::IOU {
+            maturity,
+            unit,
+            cap: None,
+        }
+    }
+
+    // coupons need a schedule that ends at maturity
+    pub fn bond(
+        start: u64,
+        maturity: u64,
+        period: u64,
+        coupon: u32,
+        unit: u64,
+    ) -> Result<Self, crate::Error> {
+        (period != 0 && start <= maturity)
+            .then_some(Terms::Bond {
+                start,
+                maturity,
+                period,
+                coupon,
+                unit,
+                cap: None,
+            })
+            .ok_or(crate::Error::BadTerms)
+    }
+
+    pub fn voucher<F: PrimeField>(expiry: u64, merchant: &Address<F>, unit: u64) -> Self {
+        Terms::Voucher {
+            expiry,
+            merchant: Self::merchant_id(merchant),
+            unit,
+            cap: None,
+        }
+    }
+
+    pub fn token(unit: u64) -> Self {
+        Terms::Token { unit, cap: None }
+    }
+
+    // limit total issued value of the asset
+    pub fn with_cap(mut self, supply_cap: u64) -> Self {
+        match &mut self {
+            Terms::IOU { cap, .. }
+            | Terms::Bond { cap, .. }
+            | Terms::Voucher { cap, .. }
+            | Terms::Token { cap, .. } => *cap = Some(supply_cap),
+        }
+        self
+    }
+
+    // maximum total issued value, `None` if uncapped
+    pub fn cap(&self) -> Option<u64> {
+        match self {
+            Terms::IOU { cap, .. }
+            | Terms::Bond { cap, .. }
+            | Terms::Voucher { cap, .. }
+            | Terms::Token { cap, .. } => *cap,
+        }
+    }
+
+    pub fn kind(&self) -> TermsKind {
+        match self {
+            Terms::IOU { .. } => TermsKind::IOU,
+            Terms::Bond { .. } => TermsKind::Bond,
+            Terms::Voucher { .. } => TermsKind::Voucher,
+            Terms::Token { .. } => TermsKind::Token,
+        }
+    }
+
+    // timestamp after which the note can be redeemed
+    pub fn maturity(&self) -> u64 {
+        match self {
+            Terms::IOU { maturity, .. } | Terms::Bond { maturity, .. } => *maturity,
+            Terms::Voucher { .. } | Terms::Token { .. } => 0,
+        }
+    }
+
+    pub fn unit(&self) -> u64 {
+        match self {
+            Terms::IOU { unit, .. }
+            | Terms::Bond { unit, .. }
+            | Terms::Voucher { unit, .. }
+            | Terms::Token { unit, .. } => *unit,
+        }
+    }
+
+    pub fn is_matured(&self, now: u64) -> bool {
+        self.maturity() <= now
+    }
+
+    // timestamp from which notes cannot be transferred, only vouchers expire
+    pub fn expiry(&self) -> u64 {
+        match self {
+            Terms::Voucher { expiry, .. } => *expiry,
+            Terms::IOU { .. } | Terms::Bond { .. } | Terms::Token { .. } => u64::MAX,
+        }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expiry() <= now
+    }
+
+    // number of coupons due at `now`, zero for anything but bonds
+    pub fn coupons(&self, now: u64) -> u64 {
+        match self {
+            Terms::Bond {
+                start,
+                maturity,
+                period,
+                ..
+            } => now
+                .min(*maturity)
+                .saturating_sub(*start)
+                .checked_div(*period)
+                .unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    // interest accrued on `value` by the coupons due at `now`. saturates at `u64::MAX`
+    pub fn accrued(&self, value: u64, now: u64) -> u64 {
+        match self {
+            Terms::Bond { coupon, .. } => {
+                let accrued = value as u128 * *coupon as u128 * self.coupons(now) as u128
+                    / BASIS_POINTS as u128;
+                accrued.try_into().unwrap_or(u64::MAX)
+            }
+            _ => 0,
+        }
+    }
+
+    // whether notes can be sent to `receiver`. vouchers only go to their merchant
+    pub fn accepts<F: PrimeField>(&self, receiver: &Address<F>) -> bool {
+        match self {
+            Terms::Voucher { merchant, .. } => *merchant == Self::merchant_id(receiver),
+            _ => true,
+        }
+    }
+
+    fn merchant_id<F: PrimeField>(merchant: &Address<F>) -> [u8; 32] {
+        let mut id = [0u8; 32];
+        id.copy_from_slice(&merchant.hash::<sha2::Sha256>());
+        id
+    }
+
+    // terms as a single field element
+    pub fn digest<F: PrimeField>(&self) -> F {
+        let bytes = sha2::Sha512::digest(self.to_bytes());
+        F::from_le_bytes_mod_order(&bytes)
     }
 
+    // version and tag followed by the fields in declaration order, cap is
+    // appended if set
     fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = vec![TERMS_VERSION, self.tag()];
         match self {
-            Terms::IOU { maturity, unit } => {
-                let mut bytes = vec![];
+            Terms::IOU { maturity, unit, .. } => {
                 bytes.extend_from_slice(&maturity.to_le_bytes());
                 bytes.extend_from_slice(&unit.to_le_bytes());
-                bytes
             }
+            Terms::Bond {
+                start,
+                maturity,
+                period,
+                coupon,
+                unit,
+                ..
+            } => {
+                bytes.extend_from_slice(&start.to_le_bytes());
+                bytes.extend_from_slice(&maturity.to_le_bytes());
+                bytes.extend_from_slice(&period.to_le_bytes());
+                bytes.extend_from_slice(&coupon.to_le_bytes());
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            }
+            Terms::Voucher {
+                expiry,
+                merchant,
+                unit,
+                ..
+            } => {
+                bytes.extend_from_slice(&expiry.to_le_bytes());
+                bytes.extend_from_slice(&merchant);
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            }
+            Terms::Token { unit, .. } => {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+        if let Some(cap) = self.cap() {
+            bytes.extend_from_slice(&cap.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+impl<F: PrimeField> CanonicalSerialize for Asset<F> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_version(&mut writer, compress)?;
+        self.issuer.serialize_with_mode(&mut writer, compress)?;
+        self.terms.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        1 + self.issuer.serialized_size(compress) + self.terms.serialized_size(compress)
+    }
+}
+
+impl<F: PrimeField> Valid for Asset<F> {
+    fn check(&self) -> Result<(), SerializationError> {
+        self.issuer.check()?;
+        self.terms.check()
+    }
+}
+
+impl<F: PrimeField> CanonicalDeserialize for Asset<F> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        deserialize_version(&mut reader, compress)?;
+        let issuer = Address::deserialize_with_mode(&mut reader, compress, Validate::No)?;
+        let terms = Terms::deserialize_with_mode(&mut reader, compress, Validate::No)?;
+        let asset = Asset { issuer, terms };
+        if validate == Validate::Yes {
+            asset.check()?;
+        }
+        Ok(asset)
+    }
+}
+
+// version of the terms layout, leads both the encoding and the digest so that
+// a new layout never commits to the same asset hash. bumped between releases only
+const TERMS_VERSION: u8 = 1;
+// terms tags, high nibble is the kind and low nibble flags optional fields
+const TAG_IOU: u8 = 0x00;
+const TAG_BOND: u8 = 0x10;
+const TAG_VOUCHER: u8 = 0x20;
+const TAG_TOKEN: u8 = 0x30;
+const TAG_KIND: u8 = 0xf0;
+// supply cap follows the fields of the kind
+const TAG_CAPPED: u8 = 0x01;
+
+impl Terms {
+    fn tag(&self) -> u8 {
+        let kind = match self {
+            Terms::IOU { .. } => TAG_IOU,
+            Terms::Bond { .. } => TAG_BOND,
+            Terms::Voucher { .. } => TAG_VOUCHER,
+            Terms::Token { .. } => TAG_TOKEN,
+        };
+        match self.cap() {
+            Some(_) => kind | TAG_CAPPED,
+            None => kind,
+        }
+    }
+}
+
+impl CanonicalSerialize for Terms {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        TERMS_VERSION.serialize_with_mode(&mut writer, compress)?;
+        self.tag().serialize_with_mode(&mut writer, compress)?;
+        match self {
+            Terms::IOU { maturity, unit, .. } => {
+                maturity.serialize_with_mode(&mut writer, compress)?;
+                unit.serialize_with_mode(&mut writer, compress)?;
+            }
+            Terms::Bond {
+                start,
+                maturity,
+                period,
+                coupon,
+                unit,
+                ..
+            } => {
+                start.serialize_with_mode(&mut writer, compress)?;
+                maturity.serialize_with_mode(&mut writer, compress)?;
+                period.serialize_with_mode(&mut writer, compress)?;
+                coupon.serialize_with_mode(&mut writer, compress)?;
+                unit.serialize_with_mode(&mut writer, compress)?;
+            }
+            Terms::Voucher {
+                expiry,
+                merchant,
+                unit,
+                ..
+            } => {
+                expiry.serialize_with_mode(&mut writer, compress)?;
+                merchant.serialize_with_mode(&mut writer, compress)?;
+                unit.serialize_with_mode(&mut writer, compress)?;
+            }
+            Terms::Token { unit, .. } => unit.serialize_with_mode(&mut writer, compress)?,
+        }
+        match self.cap() {
+            Some(cap) => cap.serialize_with_mode(&mut writer, compress),
+            None => Ok(()),
+        }
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        let size = match self {
+            Terms::IOU { .. } => 2 + 8 + 8,
+            Terms::Bond { .. } => 2 + 8 + 8 + 8 + 4 + 8,
+            Terms::Voucher { .. } => 2 + 8 + 32 + 8,
+            Terms::Token { .. } => 2 + 8,
+        };
+        size + self.cap().map_or(0, |_| 8)
+    }
+}
+
+impl Valid for Terms {
+    fn check(&self) -> Result<(), SerializationError> {
+        match self {
+            // coupons need a schedule that ends at maturity
+            Terms::Bond {
+                start,
+                maturity,
+                period,
+                ..
+            } if *period == 0 || start > maturity => Err(SerializationError::InvalidData),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl CanonicalDeserialize for Terms {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let version = u8::deserialize_with_mode(&mut reader, compress, validate)?;
+        let tag = u8::deserialize_with_mode(&mut reader, compress, validate)?;
+        if version != TERMS_VERSION || tag & !TAG_KIND & !TAG_CAPPED != 0 {
+            return Err(SerializationError::InvalidData);
+        }
+        let mut terms = match tag & TAG_KIND {
+            TAG_IOU => {
+                let maturity = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+                let unit = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+                Terms::iou(maturity, unit)
+            }
+            TAG_BOND => {
+                let start = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+                let maturity = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+                let period = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+                let coupon = u32::deserialize_with_mode(&mut reader, compress, validate)?;
+                let unit = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+                Terms::Bond {
+                    start,
+                    maturity,
+                    period,
+                    coupon,
+                    unit,
+                    cap: None,
+                }
+            }
+            TAG_VOUCHER => {
+                let expiry = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+                let merchant = <[u8; 32]>::deserialize_with_mode(&mut reader, compress, validate)?;
+                let unit = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+                Terms::Voucher {
+                    expiry,
+                    merchant,
+                    unit,
+                    cap: None,
+                }
+            }
+            TAG_TOKEN => {
+                let unit = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+                Terms::token(unit)
+            }
+            _ => return Err(SerializationError::InvalidData),
+        };
+        if tag & TAG_CAPPED != 0 {
+            let cap = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+            terms = terms.with_cap(cap);
         }
+        if validate == Validate::Yes {
+            terms.check()?;
+        }
+        Ok(terms)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Terms, TERMS_VERSION};
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+    #[test]
+    fn test_terms_encoding() {
+        assert!(matches!(
+            Terms::bond(0, 100, 0, 500, 1),
+            Err(crate::Error::BadTerms)
+        ));
+        assert!(Terms::bond(100, 0, 10, 500, 1).is_err());
+
+        let bond = Terms::bond(0, 100, 10, 500, 1).unwrap().with_cap(1000);
+        for terms in [bond, Terms::iou(10, 1), Terms::token(1)] {
+            let mut bytes = vec![];
+            terms.serialize_compressed(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), terms.compressed_size());
+            assert_eq!(Terms::deserialize_compressed(&bytes[..]).unwrap(), terms);
+
+            // unknown versions are rejected
+            assert_eq!(bytes[0], TERMS_VERSION);
+            bytes[0] += 1;
+            assert!(Terms::deserialize_compressed(&bytes[..]).is_err());
+        }
+
+        // zero period is rejected on decode too
+        let mut bytes = vec![];
+        bond.serialize_compressed(&mut bytes).unwrap();
+        bytes[2 + 8 + 8..2 + 8 + 8 + 8].fill(0);
+        assert!(Terms::deserialize_compressed(&bytes[..]).is_err());
     }
 }
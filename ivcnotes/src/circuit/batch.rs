@@ -0,0 +1,208 @@
+use super::IVC;
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ec::pairing::Pairing;
+use ark_ec::twisted_edwards::TECurveConfig;
+use ark_ec::{CurveGroup, VariableBaseMSM};
+use ark_ff::{UniformRand, Zero};
+use ark_groth16::{Groth16, Proof, VerifyingKey};
+use ark_relations::r1cs::SynthesisError;
+use rand::{CryptoRng, RngCore};
+use std::marker::PhantomData;
+
+// pairing curve and inner twisted edwards curve of a groth16 instance of `IVC`
+pub trait Groth16Curve: Clone {
+    type Pairing: Pairing;
+    type TE: TECurveConfig<BaseField = <Self::Pairing as Pairing>::ScalarField> + Clone;
+    const CURVE_ID: &'static str;
+}
+
+#[derive(Debug)]
+// groth16 instance of `IVC`, histories are checked with `groth16` batches
+pub struct Groth16IVC<C: Groth16Curve>(PhantomData<C>);
+
+impl<C: Groth16Curve> Clone for Groth16IVC<C> {
+    fn clone(&self) -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<C: Groth16Curve> IVC for Groth16IVC<C>
+where
+    <C::Pairing as Pairing>::ScalarField: Absorb,
+{
+    type Snark = Groth16<C::Pairing>;
+    type Field = <C::Pairing as Pairing>::ScalarField;
+    type TE = C::TE;
+    const CURVE_ID: &'static str = C::CURVE_ID;
+
+    fn verify_batch<R: RngCore + CryptoRng>(
+        vk: &VerifyingKey<C::Pairing>,
+        instances: &[(Vec<Self::Field>, &Proof<C::Pairing>)],
+        rng: &mut R,
+    ) -> Result<bool, SynthesisError> {
+        groth16(vk, instances, rng)
+    }
+}
+
+// batch check of groth16 proofs under the same key. verification equations
+// e(a, b) = e(alpha, beta) * e(l, gamma) * e(c, delta) are combined with random
+// coefficients into a single multi pairing. a passing batch means that every proof
+// verifies, except with negligible probability
+pub fn groth16<P: Pairing, R: RngCore + CryptoRng>(
+    vk: &VerifyingKey<P>,
+    instances: &[(Vec<P::ScalarField>, &Proof<P>)],
+    rng: &mut R,
+) -> Result<bool, SynthesisError> {
+    // coefficients of the input bases, the first one is the sum of coefficients
+    let mut inputs = vec![P::ScalarField::zero(); vk.gamma_abc_g1.len()];
+    let mut coeffs = Vec::with_capacity(instances.len());
+    let mut g1 = Vec::with_capacity(instances.len() + 3);
+    let mut g2 = Vec::with_capacity(instances.len() + 3);
+    for (input, proof) in instances {
+        (input.len() + 1 == vk.gamma_abc_g1.len())
+            .then_some(())
+            .ok_or(SynthesisError::MalformedVerifyingKey)?;
+        let r = P::ScalarField::rand(rng);
+        inputs[0] += r;
+        for (acc, x) in inputs.iter_mut().skip(1).zip(input.iter()) {
+            *acc += r * x;
+        }
+        g1.push(proof.a * r);
+        g2.push(proof.b);
+        coeffs.push(r);
+    }
+
+    let c = instances
+        .iter()
+        .map(|(_, proof)| proof.c)
+        .collect::<Vec<_>>();
+    g1.push(-(vk.alpha_g1 * inputs[0]));
+    g1.push(-P::G1::msm_unchecked(&vk.gamma_abc_g1, &inputs));
+    g1.push(-P::G1::msm_unchecked(&c, &coeffs));
+    g2.extend([vk.beta_g2, vk.gamma_g2, vk.delta_g2]);
+
+    Ok(P::multi_pairing(P::G1::normalize_batch(&g1), g2).is_zero())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{groth16, Groth16Curve, Groth16IVC};
+    use crate::asset::{Asset, Terms};
+    use crate::circuit::{setup, Prover, Verifier};
+    use crate::id::Auth;
+    use crate::note::StepPath;
+    use crate::poseidon::PoseidonConfigs;
+    use crate::seed::{DerivationPath, Seed};
+    use crate::wallet::registry::MemoryRegistry;
+    use crate::wallet::{CommReceiver, Wallet};
+    use ark_bn254::{Bn254, Fr};
+    use ark_crypto_primitives::snark::SNARK;
+    use ark_ff::UniformRand;
+    use ark_groth16::Groth16;
+    use ark_r1cs_std::{alloc::AllocVar, eq::EqGadget, fields::fp::FpVar};
+    use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // knows `x` and `y` with `x * y` and `x + y` public
+    struct Toy(Option<(Fr, Fr)>);
+
+    impl ConstraintSynthesizer<Fr> for Toy {
+        fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+            let witness = self.0.ok_or(SynthesisError::AssignmentMissing);
+            let product = FpVar::new_input(cs.clone(), || witness.map(|(x, y)| x * y))?;
+            let sum = FpVar::new_input(cs.clone(), || witness.map(|(x, y)| x + y))?;
+            let x = FpVar::new_witness(cs.clone(), || witness.map(|(x, _)| x))?;
+            let y = FpVar::new_witness(cs, || witness.map(|(_, y)| y))?;
+            (&x * &y).enforce_equal(&product)?;
+            (&x + &y).enforce_equal(&sum)
+        }
+    }
+
+    #[test]
+    fn test_groth16_batch() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let (pk, vk) = Groth16::<Bn254>::circuit_specific_setup(Toy(None), &mut rng).unwrap();
+        let proofs = (0..4)
+            .map(|_| {
+                let (x, y) = (Fr::rand(&mut rng), Fr::rand(&mut rng));
+                let proof = Groth16::<Bn254>::prove(&pk, Toy(Some((x, y))), &mut rng).unwrap();
+                (vec![x * y, x + y], proof)
+            })
+            .collect::<Vec<_>>();
+        let instances = proofs
+            .iter()
+            .map(|(input, proof)| (input.clone(), proof))
+            .collect::<Vec<_>>();
+        assert!(groth16(&vk, &instances, &mut rng).unwrap());
+        assert!(groth16::<Bn254, _>(&vk, &[], &mut rng).unwrap());
+
+        // a bad public input fails the batch
+        let mut bad = instances.clone();
+        bad[2].0[1] += Fr::from(1);
+        assert!(!groth16(&vk, &bad, &mut rng).unwrap());
+        // and so does a proof of another instance
+        let mut bad = instances.clone();
+        bad[3].1 = instances[0].1;
+        assert!(!groth16(&vk, &bad, &mut rng).unwrap());
+        // while the per proof check agrees on which one fails
+        let failing = bad
+            .iter()
+            .position(|(input, proof)| !Groth16::<Bn254>::verify(&vk, input, proof).unwrap());
+        assert_eq!(failing, Some(3));
+
+        let mut short = instances.clone();
+        short[0].0.pop();
+        assert!(groth16(&vk, &short, &mut rng).is_err());
+    }
+
+    #[derive(Clone, Debug)]
+    struct Bls;
+
+    impl Groth16Curve for Bls {
+        type Pairing = ark_bls12_381::Bls12_381;
+        type TE = ark_ed_on_bls12_381_bandersnatch::EdwardsConfig;
+        const CURVE_ID: &'static str = "bls12-381";
+    }
+
+    type E = Groth16IVC<Bls>;
+
+    #[test]
+    fn test_groth16_ivc() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let h = PoseidonConfigs::<ark_bls12_381::Fr>::generate();
+        let (prover, verifier) = setup::<E, _>(&h, &mut rng).unwrap();
+        let shared = Rc::new(RefCell::new(MemoryRegistry::new()));
+        let wallet = |auth: Auth<E>| {
+            let prover = Prover {
+                pk: prover.pk.clone(),
+            };
+            let verifier = Verifier {
+                vk: verifier.vk.clone(),
+            };
+            Wallet::new(auth, &h, prover, verifier, shared.clone())
+        };
+        let seed = Seed::from_bytes([1; 64]);
+        let bob = || Auth::derive(&h, &seed, &DerivationPath::account(0));
+        let mut issuer = wallet(Auth::generate(&h, &mut rng).unwrap());
+        let mut alice = wallet(Auth::generate(&h, &mut rng).unwrap());
+        let mut bob_here = wallet(bob());
+
+        let asset = Asset::new(CommReceiver::address(&issuer), &Terms::iou(0, 1));
+        issuer.issue(&mut rng, 0, &mut alice, &asset, 10).unwrap();
+        alice.split(&mut rng, 0, &mut bob_here, 0, 4).unwrap();
+        let history = bob_here.accounts()[0].spendables()[0].clone();
+
+        // the batch fails and the fallback names the step with the wrong proof
+        let mut bob_there = wallet(bob());
+        let mut tampered = history.clone();
+        tampered.steps[1].proof = tampered.steps[0].proof.clone();
+        match bob_there.receive(&tampered) {
+            Err(crate::Error::VerificationFailed { step }) => assert_eq!(step, StepPath::at(1)),
+            result => panic!("unexpected {:?}", result),
+        }
+        bob_there.receive(&history).unwrap();
+        assert_eq!(bob_there.accounts()[0].spendables()[0].value(), 4);
+    }
+}
@@ -1,192 +1,276 @@
 use crate::note::NoteOutIndex;
+use crate::poseidon::STATE_DEPTH;
+use crate::tx::MAX_OUTPUTS;
 use ark_ff::Field;
 use ark_r1cs_std::alloc::AllocVar;
 use ark_r1cs_std::boolean::Boolean;
 use ark_r1cs_std::eq::EqGadget;
 use ark_r1cs_std::fields::fp::FpVar;
 use ark_r1cs_std::fields::nonnative::NonNativeFieldVar;
+use ark_r1cs_std::fields::FieldVar;
 use ark_r1cs_std::select::CondSelectGadget;
 use ark_relations::r1cs::{ConstraintSystemRef, Result as CSResult, SynthesisError};
 
-use super::inputs::{witness_in, witness_point_in, NoteVar, PublicInputVar};
+use super::inputs::{
+    witness_bool_in, witness_in, witness_point_in, NoteOpening, NoteVar, PublicInputVar,
+};
 use super::{verify_signature, Circuit, IVC};
 
+// recover hashes, input state and nullifier of a note spent by the sender. returns
+// blind note hash, note hash, value, input state and nullifier
+#[allow(clippy::type_complexity)]
+fn spent_note<E: IVC>(
+    cs: ConstraintSystemRef<E::Field>,
+    cir: &Circuit<E>,
+    pi: &PublicInputVar<E::Field>,
+    sender: &FpVar<E::Field>,
+    nullifier_key: &FpVar<E::Field>,
+    opening: Option<&NoteOpening<E::Field>>,
+) -> CSResult<(
+    FpVar<E::Field>,
+    FpVar<E::Field>,
+    FpVar<E::Field>,
+    FpVar<E::Field>,
+    FpVar<E::Field>,
+)> {
+    let one = FpVar::new_constant(cs.clone(), E::Field::ONE)?;
+
+    let parent_note = witness_in(cs.clone(), opening, |e| e.parent)?;
+    let step = witness_in(cs.clone(), opening, |e| E::Field::from(e.step as u64))?;
+    let value = witness_in(cs.clone(), opening, |e| E::Field::from(e.value))?;
+    let blind = witness_in(cs.clone(), opening, |e| e.blind)?;
+    let path = (0..STATE_DEPTH)
+        .map(|level| witness_in(cs.clone(), opening, |e| e.path[level]))
+        .collect::<CSResult<Vec<_>>>()?;
+
+    // index is recovered from the leaf position, issued note is the first leaf
+    let is_issue = witness_bool_in(cs.clone(), opening, |e| e.index == NoteOutIndex::Issue)?;
+    let position = (0..STATE_DEPTH)
+        .map(|level| {
+            witness_bool_in(cs.clone(), opening, |e| {
+                (e.index.position() >> level) & 1 == 1
+            })
+        })
+        .collect::<CSResult<Vec<_>>>()?;
+    for bit in position.iter() {
+        bit.conditional_enforce_equal(&Boolean::FALSE, &is_issue)?;
+    }
+    let index = position
+        .iter()
+        .rev()
+        .try_fold(FpVar::zero(), |acc, bit| -> CSResult<_> {
+            Ok(acc.double()? + FpVar::from(bit.clone()))
+        })?
+        + one
+        - FpVar::from(is_issue);
+
+    let note = NoteVar::new(&pi.asset_hash, sender, &value, &step, &parent_note, &index);
+    let note_hash = cir.h.var_note(cs.clone(), &note)?;
+    let blind_note_hash = cir.h.var_blind_note(cs.clone(), &note_hash, &blind)?;
+
+    let state_in = cir
+        .h
+        .var_state_from_path(cs.clone(), &blind_note_hash, &position, &path)?;
+
+    let nullifier = cir.h.var_nullifier(cs.clone(), &note_hash, nullifier_key)?;
+
+    Ok((blind_note_hash, note_hash, value, state_in, nullifier))
+}
+
 pub(crate) fn synth<E: IVC>(cs: ConstraintSystemRef<E::Field>, cir: Circuit<E>) -> CSResult<()> {
     let pi = cir.public.as_ref();
     let aux = cir.aux.as_ref();
 
     let zero = E::Field::ZERO;
     let const_zero = FpVar::new_constant(cs.clone(), zero)?;
-    let const_true = Boolean::new_constant(cs.clone(), true)?;
-
-    let index_issue =
-        FpVar::new_constant(cs.clone(), (NoteOutIndex::Issue {}).inner::<E::Field>())?;
-    let index_0 = FpVar::new_constant(cs.clone(), (NoteOutIndex::Out0 {}).inner::<E::Field>())?;
-    let index_1 = FpVar::new_constant(cs.clone(), (NoteOutIndex::Out1 {}).inner::<E::Field>())?;
+    let const_max = FpVar::new_constant(cs.clone(), E::Field::from(u64::MAX))?;
 
     let pi = PublicInputVar::new(cs.clone(), pi)?;
 
-    // identity commitment integrity
+    // identity commitment integrity. the sender stays a witness, spends bind it
+    // through the owner of the spent note and issues through the asset issuer
     let pubkey = witness_point_in(cs.clone(), aux, |e| *e.public_key.as_ref())?;
     let nullifier_key = witness_in(cs.clone(), aux, |e| e.nullifier_key)?;
     let sender = cir
         .h
         .var_id_commitment(cs.clone(), &nullifier_key, &pubkey)?;
-    pi.sender.enforce_equal(&sender)?;
 
-    // Branch 1: IssueTx
-    let is_issue_tx = pi.step.is_eq(&const_zero)?;
-    let (sighash_issue, _note_hash, is_issue_tx) = {
-        let value = witness_in(cs.clone(), aux, |e| E::Field::from(e.value_out))?;
-        let blind = witness_in(cs.clone(), aux, |e| e.blind_out_0)?;
-        let note = NoteVar::new(
-            &pi.asset_hash,
-            &pi.sender,
-            &value,
-            &pi.step,
-            &const_zero,
-            &index_issue,
-        );
-
-        // recover note hash
-        let note_hash = cir.h.var_note(cs.clone(), &note)?;
-        // recover blind note hash
-        let blind_note_hash = cir.h.var_blind_note(cs.clone(), &note_hash, &blind)?;
-
-        // initial state is asset hash. match it
-        pi.state_in
-            .conditional_enforce_equal(&pi.asset_hash, &is_issue_tx)?;
-
-        // recover the output state
-        let state_out = cir.h.var_state(cs.clone(), &const_zero, &blind_note_hash)?;
-
-        pi.state_out
-            .conditional_enforce_equal(&state_out, &is_issue_tx)?;
-
-        // recover sighash
-        let sighash = cir
-            .h
-            .var_sighash(cs.clone(), &const_zero, &const_zero, &note_hash)?;
-
-        (sighash, note_hash, is_issue_tx)
-    };
-
-    // Branch 2: SplitTx
-    let sighash_split = {
-        let is_split_tx = is_issue_tx.not();
-
-        // enforce input state integrity
-        let (blind_note_in_hash, note_in_hash, value_in) = {
-            let sibling = witness_in(cs.clone(), aux, |e| e.sibling)?;
-            let value = witness_in(cs.clone(), aux, |e| E::Field::from(e.value_in))?;
-            let blind = witness_in(cs.clone(), aux, |e| e.blind_in)?;
-            let parent_note = witness_in(cs.clone(), aux, |e| e.parent)?;
-
-            let index = witness_in(cs.clone(), aux, |e| e.input_index.inner::<E::Field>())?;
-            // enforce index to be either ::Out0 or ::Out1
-            let is_i0 = index.is_eq(&index_0)?;
-            let is_i1 = index.is_eq(&index_1)?;
-            is_i0.or(&is_i1)?.enforce_equal(&const_true)?;
-
-            let note_in = NoteVar::new(
-                &pi.asset_hash,
-                &pi.sender,
-                &value,
-                &pi.step,
-                &parent_note,
-                &index,
-            );
-
-            // recover note hash
-            let note_hash = cir.h.var_note(cs.clone(), &note_in)?;
-
-            // recover blinded note hash
-            let blind_note_hash = cir.h.var_blind_note(cs.clone(), &note_hash, &blind)?;
-
-            // recover input state
-            let lhs = CondSelectGadget::conditionally_select(&is_i0, &blind_note_hash, &sibling)?;
-            let rhs = CondSelectGadget::conditionally_select(&is_i1, &sibling, &blind_note_hash)?;
-            let state_in = cir.h.var_state(cs.clone(), &lhs, &rhs)?;
-
-            // match with public input
-            pi.state_in
-                .conditional_enforce_equal(&state_in, &is_split_tx)?;
-
-            // enforce nullifier integrity
-            let nullifier = cir
-                .h
-                .var_nullifier(cs.clone(), &note_hash, &nullifier_key)?;
-
-            // match with public input
-            pi.nullifier
-                .conditional_enforce_equal(&nullifier, &is_split_tx)?;
-
-            (blind_note_hash, note_hash, value)
-        };
+    // asset hash integrity, maturity and expiry come from the public input
+    let issuer = witness_in(cs.clone(), aux, |e| *e.asset.issuer().as_ref())?;
+    let terms = witness_in(cs.clone(), aux, |e| e.asset.terms().digest::<E::Field>())?;
+    let cap = witness_in(cs.clone(), aux, |e| {
+        E::Field::from(e.asset.terms().cap().unwrap_or(u64::MAX))
+    })?;
+    let asset_hash =
+        cir.h
+            .var_asset(cs.clone(), &issuer, &terms, &pi.maturity, &pi.expiry, &cap)?;
+    pi.asset_hash.enforce_equal(&asset_hash)?;
 
-        // enforce output state integrity
-        let (note_out_hash_0, note_out_hash_1) = {
-            let value_out_1 = witness_in(cs.clone(), aux, |e| E::Field::from(e.value_out))?;
-            let blind_1 = witness_in(cs.clone(), aux, |e| e.blind_out_1)?;
-            let note_out_1 = NoteVar {
-                asset_hash: pi.asset_hash.clone(),
-                owner: pi.sender.clone(),
-                value: value_out_1.clone(),
-                step: pi.step.clone(),
-                parent_note: blind_note_in_hash.clone(),
-                out_index: index_1,
-            };
-            // recover note hash
-            let note_hash_1 = cir.h.var_note(cs.clone(), &note_out_1)?;
-
-            // recover blinded note hash
-            let blind_note_hash_1 = cir.h.var_blind_note(cs.clone(), &note_hash_1, &blind_1)?;
-            let value_out_0 = value_in - &value_out_1;
-
-            let max = FpVar::new_constant(cs.clone(), E::Field::from(u64::MAX))?;
-            value_out_0.enforce_cmp(&value_out_1, std::cmp::Ordering::Less, true)?;
-            value_out_1.enforce_cmp(&max, std::cmp::Ordering::Less, true)?; // maybe not required
-
-            let blind_0 = witness_in(cs.clone(), aux, |e| e.blind_out_0)?;
-            let receiver = witness_in(cs.clone(), aux, |e| e.receiver)?;
-            let note_out_0 = NoteVar {
-                asset_hash: pi.asset_hash.clone(),
-                owner: receiver,
-                value: value_out_0.clone(),
-                step: pi.step.clone(),
-                parent_note: blind_note_in_hash,
-                out_index: index_0,
-            };
-            // recover note hash
-            let note_hash_0 = cir.h.var_note(cs.clone(), &note_out_0)?;
-
-            // recover blinded note hash
-            let blind_note_hash_0 = cir.h.var_blind_note(cs.clone(), &note_hash_1, &blind_0)?;
-
-            // recover the output state
-            let state_out = cir
-                .h
-                .var_state(cs.clone(), &blind_note_hash_0, &blind_note_hash_1)?;
-
-            // match with public input
-            pi.state_out
-                .conditional_enforce_equal(&state_out, &is_split_tx)?;
-
-            (note_hash_0, note_hash_1)
+    // transaction kind. issue starts the chain, merge spends a second note,
+    // redeem ends the chain with no outputs and split is everything else
+    let is_issue_tx = pi.step.is_eq(&const_zero)?;
+    let is_merge_tx = pi.nullifier_1.is_neq(&const_zero)?;
+    let is_redeem_tx = pi.state_out.is_eq(&const_zero)?;
+    is_issue_tx
+        .and(&is_merge_tx)?
+        .enforce_equal(&Boolean::FALSE)?;
+    is_redeem_tx
+        .and(&is_issue_tx.or(&is_merge_tx)?)?
+        .enforce_equal(&Boolean::FALSE)?;
+    let is_spend_tx = is_issue_tx.not();
+    let is_split_tx = is_spend_tx
+        .and(&is_merge_tx.not())?
+        .and(&is_redeem_tx.not())?;
+
+    // redemption waits for maturity and transfers stop at expiry
+    let is_matured = pi
+        .now
+        .is_cmp(&pi.maturity, std::cmp::Ordering::Greater, true)?;
+    is_matured.conditional_enforce_equal(&Boolean::TRUE, &is_redeem_tx)?;
+    let is_expired = pi
+        .now
+        .is_cmp(&pi.expiry, std::cmp::Ordering::Greater, true)?;
+    is_expired
+        .and(&is_split_tx.or(&is_merge_tx)?)?
+        .enforce_equal(&Boolean::FALSE)?;
+
+    // only the issuer of the asset creates step zero notes
+    sender.conditional_enforce_equal(&issuer, &is_issue_tx)?;
+    // initial state is asset hash and nothing is nullified
+    pi.state_in
+        .conditional_enforce_equal(&pi.asset_hash, &is_issue_tx)?;
+    pi.nullifier
+        .conditional_enforce_equal(&const_zero, &is_issue_tx)?;
+    // second input state is only set by merge transactions
+    pi.state_in_1
+        .conditional_enforce_equal(&const_zero, &is_merge_tx.not())?;
+
+    // first input, spent by split and merge
+    let (blind_note_in_hash_0, note_in_hash_0, value_in_0, state_in_0, nullifier_0) = spent_note(
+        cs.clone(),
+        &cir,
+        &pi,
+        &sender,
+        &nullifier_key,
+        aux.map(|e| &e.inputs[0]),
+    )?;
+    pi.state_in
+        .conditional_enforce_equal(&state_in_0, &is_spend_tx)?;
+    pi.nullifier
+        .conditional_enforce_equal(&nullifier_0, &is_spend_tx)?;
+
+    // second input, spent by merge only
+    let (_, note_in_hash_1, value_in_1, state_in_1, nullifier_1) = spent_note(
+        cs.clone(),
+        &cir,
+        &pi,
+        &sender,
+        &nullifier_key,
+        aux.map(|e| &e.inputs[1]),
+    )?;
+    pi.state_in_1
+        .conditional_enforce_equal(&state_in_1, &is_merge_tx)?;
+    pi.nullifier_1
+        .conditional_enforce_equal(&nullifier_1, &is_merge_tx)?;
+    // a note cannot be merged with itself
+    pi.nullifier
+        .conditional_enforce_not_equal(&pi.nullifier_1, &is_merge_tx)?;
+
+    // outputs. issue and merge have a single output at the first position, split
+    // may use any of the slots. unused slots are zero leaves
+    let is_single_output = is_issue_tx.or(&is_merge_tx)?;
+    let parent = is_issue_tx.select(&const_zero, &blind_note_in_hash_0)?;
+    let mut leaves = vec![];
+    let mut value_out = const_zero.clone();
+    for k in 0..MAX_OUTPUTS {
+        let output = aux.map(|e| &e.outputs[k]);
+        let used = witness_bool_in(cs.clone(), output, |e| e.used)?;
+        let owner = witness_in(cs.clone(), output, |e| e.owner)?;
+        let value = witness_in(cs.clone(), output, |e| E::Field::from(e.value))?;
+        let blind = witness_in(cs.clone(), output, |e| e.blind)?;
+
+        value.enforce_cmp(&const_max, std::cmp::Ordering::Less, true)?;
+        value.conditional_enforce_equal(&const_zero, &used.not())?;
+
+        let index = match k {
+            0 => {
+                let index_0 = NoteOutIndex::FIRST_OUT.inner::<E::Field>();
+                FpVar::new_constant(cs.clone(), index_0)? - FpVar::from(is_issue_tx.clone())
+            }
+            _ => {
+                let index_k = NoteOutIndex::out(k).expect("k is below MAX_OUTPUTS");
+                FpVar::new_constant(cs.clone(), index_k.inner::<E::Field>())?
+            }
         };
 
-        // recover sighash
-        cir.h.var_sighash(
-            cs.clone(),
-            &note_in_hash,
-            &note_out_hash_0,
-            &note_out_hash_1,
-        )?
-    };
+        match k {
+            0 => {
+                used.conditional_enforce_equal(&Boolean::TRUE, &is_single_output)?;
+                // merged note stays with the sender
+                owner.conditional_enforce_equal(&sender, &is_merge_tx)?;
+            }
+            _ => used.conditional_enforce_equal(&Boolean::FALSE, &is_single_output)?,
+        }
+        used.conditional_enforce_equal(&Boolean::FALSE, &is_redeem_tx)?;
+
+        let note = NoteVar::new(&pi.asset_hash, &owner, &value, &pi.step, &parent, &index);
+        let note_hash = cir.h.var_note(cs.clone(), &note)?;
+        let blind_note_hash = cir.h.var_blind_note(cs.clone(), &note_hash, &blind)?;
 
-    // select sighash based on the tx type
+        leaves.push(used.select(&blind_note_hash, &const_zero)?);
+        value_out += value;
+    }
+
+    // value conservation, issuer is free to choose the issued value
+    value_out.conditional_enforce_equal(&value_in_0, &is_split_tx)?;
+    value_out.conditional_enforce_equal(&(&value_in_0 + &value_in_1), &is_merge_tx)?;
+    // redeemed value is public so that the issuer can account for it
+    let redeemed = is_redeem_tx.select(&value_in_0, &const_zero)?;
+    pi.redeemed.enforce_equal(&redeemed)?;
+
+    // supply counter of the issuer. zero input commitment opens the counter and
+    // the issued value is added to it within the cap of the asset
+    let issued_in = witness_in(cs.clone(), aux, |e| E::Field::from(e.supply.issued))?;
+    let supply_blind_in = witness_in(cs.clone(), aux, |e| e.supply.blind_in)?;
+    let supply_blind_out = witness_in(cs.clone(), aux, |e| e.supply.blind_out)?;
+    issued_in.enforce_cmp(&const_max, std::cmp::Ordering::Less, true)?;
+    let is_first_issue = pi.supply_in.is_eq(&const_zero)?;
+    issued_in.conditional_enforce_equal(&const_zero, &is_first_issue)?;
+    let supply_in = cir
+        .h
+        .var_supply(cs.clone(), &pi.asset_hash, &issued_in, &supply_blind_in)?;
+    pi.supply_in
+        .conditional_enforce_equal(&supply_in, &is_issue_tx.and(&is_first_issue.not())?)?;
+    let issued_out = &issued_in + &value_out;
+    issued_out
+        .is_cmp(&cap, std::cmp::Ordering::Less, true)?
+        .conditional_enforce_equal(&Boolean::TRUE, &is_issue_tx)?;
+    let supply_out =
+        cir.h
+            .var_supply(cs.clone(), &pi.asset_hash, &issued_out, &supply_blind_out)?;
+    pi.supply_out
+        .conditional_enforce_equal(&supply_out, &is_issue_tx)?;
+    // transfers carry no supply commitments
+    pi.supply_in
+        .conditional_enforce_equal(&const_zero, &is_spend_tx)?;
+    pi.supply_out
+        .conditional_enforce_equal(&const_zero, &is_spend_tx)?;
+
+    // output state commits to all output slots
+    let state_out = cir.h.var_state_root(cs.clone(), &leaves)?;
+    pi.state_out
+        .conditional_enforce_equal(&state_out, &is_redeem_tx.not())?;
+
+    // recover sighash, input of an issue tx is zero
+    let note_in_hash = is_issue_tx.select(&const_zero, &note_in_hash_0)?;
+    let sighash_tx = cir
+        .h
+        .var_sighash(cs.clone(), &note_in_hash, &pi.state_out)?;
+    let sighash_merge =
+        cir.h
+            .var_sighash_merge(cs.clone(), &note_in_hash_0, &note_in_hash_1, &pi.state_out)?;
     let sighash =
-        CondSelectGadget::conditionally_select(&is_issue_tx, &sighash_issue, &sighash_split)?;
+        CondSelectGadget::conditionally_select(&is_merge_tx, &sighash_merge, &sighash_tx)?;
 
     // recover signature & verify
     let sig_r = witness_point_in(cs.clone(), aux, |e| *e.signature.r())?;
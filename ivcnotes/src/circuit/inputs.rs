@@ -1,11 +1,16 @@
 use super::IVC;
-use crate::note::NoteOutIndex;
-use crate::poseidon::ToCRH;
-use crate::{Address, AssetHash, Blind, BlindNoteHash, FWrap, Nullifier, NullifierKey, StateHash};
+use crate::asset::{Asset, Terms};
+use crate::note::{Issuance, Note, NoteOutIndex};
+use crate::poseidon::{ToCRH, STATE_DEPTH};
+use crate::tx::MAX_OUTPUTS;
+use crate::{
+    Address, AssetHash, Blind, BlindNoteHash, FWrap, Nullifier, NullifierKey, StateHash, SupplyHash,
+};
 use ark_ec::twisted_edwards::Affine;
 use ark_ec::twisted_edwards::TECurveConfig;
 use ark_ff::PrimeField;
 use ark_r1cs_std::alloc::{AllocVar, AllocationMode};
+use ark_r1cs_std::boolean::Boolean;
 use ark_r1cs_std::fields::fp::FpVar;
 use ark_r1cs_std::groups::curves::twisted_edwards::AffineVar;
 use ark_relations::r1cs::{Namespace, Result as CSResult, SynthesisError};
@@ -38,6 +43,14 @@ pub(super) fn witness_in<Z, F: PrimeField, T: Borrow<F> + Clone>(
     var_in::<_, _, _, _, _>(cs.into().cs(), st, access, AllocationMode::Witness)
 }
 
+pub(super) fn witness_bool_in<Z, F: PrimeField>(
+    cs: impl Into<Namespace<F>>,
+    st: Option<&Z>,
+    access: impl FnOnce(&Z) -> bool,
+) -> CSResult<Boolean<F>> {
+    var_in::<_, _, _, Boolean<F>, bool>(cs.into().cs(), st, access, AllocationMode::Witness)
+}
+
 pub(super) fn witness_point_in<Z, F: PrimeField, TE: TECurveConfig<BaseField = F> + Clone>(
     cs: impl Into<Namespace<F>>,
     st: Option<&Z>,
@@ -54,7 +67,6 @@ pub(super) fn witness_point_in<Z, F: PrimeField, TE: TECurveConfig<BaseField = F
 impl<F: PrimeField> PublicInput<F> {
     pub(crate) fn new(
         asset_hash: &AssetHash<F>,
-        sender: &Address<F>,
         state_in: &StateHash<F>,
         state_out: &StateHash<F>,
         step: u32,
@@ -62,22 +74,69 @@ impl<F: PrimeField> PublicInput<F> {
     ) -> Self {
         Self {
             asset_hash: *asset_hash,
-            sender: *sender,
             state_in: *state_in,
             state_out: *state_out,
             step,
             nullifier: *nullifier,
+            state_in_1: StateHash::default(),
+            nullifier_1: Nullifier::default(),
+            redeemed: 0,
+            now: 0,
+            maturity: 0,
+            expiry: 0,
+            supply_in: SupplyHash::default(),
+            supply_out: SupplyHash::default(),
         }
     }
 
+    // time of the step and the time bounds of the asset terms
+    pub(crate) fn with_time(mut self, now: u64, terms: &Terms) -> Self {
+        self.now = now;
+        self.maturity = terms.maturity();
+        self.expiry = terms.expiry();
+        self
+    }
+
+    // second input of a merge transaction
+    pub(crate) fn with_merge(
+        mut self,
+        state_in_1: &StateHash<F>,
+        nullifier_1: &Nullifier<F>,
+    ) -> Self {
+        self.state_in_1 = *state_in_1;
+        self.nullifier_1 = *nullifier_1;
+        self
+    }
+
+    // supply commitments of the issuer around an issue transaction
+    pub(crate) fn with_supply(mut self, issuance: &Issuance<F>) -> Self {
+        self.supply_in = issuance.supply_in;
+        self.supply_out = issuance.supply_out;
+        self
+    }
+
+    // value retired by a redeem transaction
+    pub(crate) fn with_redeem(mut self, redeemed: u64) -> Self {
+        self.redeemed = redeemed;
+        self
+    }
+
+    // in the allocation order of `PublicInputVar::new`
     pub(crate) fn to_verifier(&self) -> Vec<F> {
         vec![
             self.asset_hash.inner(),
-            self.sender.inner(),
             self.state_in.inner(),
             self.state_out.inner(),
-            F::from(self.step as u64),
             self.nullifier.inner(),
+            F::from(self.step as u64),
+            self.state_in_1.inner(),
+            self.nullifier_1.inner(),
+            F::from(self.redeemed),
+            F::from(self.now),
+            F::from(self.maturity),
+            F::from(self.expiry),
+            self.supply_in.inner(),
+            self.supply_out.inner(),
         ]
     }
 }
@@ -86,26 +145,48 @@ impl<F: PrimeField> PublicInput<F> {
 pub struct PublicInput<F: PrimeField> {
     // asset hash is part of notes
     pub(crate) asset_hash: AssetHash<F>,
-    // sender of the note
-    pub(crate) sender: Address<F>,
-    // input state `state_in = hash(sibling, input_note)` or `state_in = hash(input_note, sibling)`
+    // input state, merkle root of the outputs that created the input note
     pub(crate) state_in: StateHash<F>,
-    // output state `state_out = hash(note_out_0, note_out_1)`
+    // output state, merkle root of the outputs with zero leaves for unused slots.
+    // zero when the note is redeemed
     pub(crate) state_out: StateHash<F>,
     // number of steps so far in the ivc propagation
     pub(crate) step: u32,
     // nullifier of the spent note
     pub(crate) nullifier: Nullifier<F>,
+    // input state of the second note of a merge, zero otherwise
+    pub(crate) state_in_1: StateHash<F>,
+    // nullifier of the second note of a merge, zero otherwise
+    pub(crate) nullifier_1: Nullifier<F>,
+    // value of the redeemed note, zero otherwise
+    pub(crate) redeemed: u64,
+    // time of the step, chosen by the prover and checked by receivers
+    pub(crate) now: u64,
+    // maturity and expiry are bound to the asset hash in-circuit, verifiers take
+    // them from the terms of the asset
+    pub(crate) maturity: u64,
+    pub(crate) expiry: u64,
+    // issued supply commitments before and after an issue transaction, zero
+    // otherwise. zero input supply opens the counter of the asset
+    pub(crate) supply_in: SupplyHash<F>,
+    pub(crate) supply_out: SupplyHash<F>,
 }
 
 #[derive(Debug, Clone)]
 pub struct PublicInputVar<F: PrimeField> {
     pub(crate) asset_hash: FpVar<F>,
-    pub(crate) sender: FpVar<F>,
     pub(crate) state_in: FpVar<F>,
     pub(crate) state_out: FpVar<F>,
     pub(crate) step: FpVar<F>,
     pub(crate) nullifier: FpVar<F>,
+    pub(crate) state_in_1: FpVar<F>,
+    pub(crate) nullifier_1: FpVar<F>,
+    pub(crate) redeemed: FpVar<F>,
+    pub(crate) now: FpVar<F>,
+    pub(crate) maturity: FpVar<F>,
+    pub(crate) expiry: FpVar<F>,
+    pub(crate) supply_in: FpVar<F>,
+    pub(crate) supply_out: FpVar<F>,
 }
 
 impl<F: PrimeField> PublicInputVar<F> {
@@ -123,81 +204,165 @@ impl<F: PrimeField> PublicInputVar<F> {
     ) -> CSResult<PublicInputVar<F>> {
         let cs = cs.into().cs();
         let asset_hash = Self::input_in(cs.clone(), pi, |e| e.asset_hash)?;
-        let sender = Self::input_in(cs.clone(), pi, |e| e.sender)?;
         let state_in = Self::input_in(cs.clone(), pi, |e| e.state_in)?;
         let state_out = Self::input_in(cs.clone(), pi, |e| e.state_out)?;
         let nullifier = Self::input_in(cs.clone(), pi, |e| e.nullifier)?;
         let step = Self::input_in(cs.clone(), pi, |e| F::from(e.step as u64))?;
+        let state_in_1 = Self::input_in(cs.clone(), pi, |e| e.state_in_1)?;
+        let nullifier_1 = Self::input_in(cs.clone(), pi, |e| e.nullifier_1)?;
+        let redeemed = Self::input_in(cs.clone(), pi, |e| F::from(e.redeemed))?;
+        let now = Self::input_in(cs.clone(), pi, |e| F::from(e.now))?;
+        let maturity = Self::input_in(cs.clone(), pi, |e| F::from(e.maturity))?;
+        let expiry = Self::input_in(cs.clone(), pi, |e| F::from(e.expiry))?;
+        let supply_in = Self::input_in(cs.clone(), pi, |e| e.supply_in)?;
+        let supply_out = Self::input_in(cs.clone(), pi, |e| e.supply_out)?;
         Ok(PublicInputVar {
             asset_hash,
-            sender,
             state_in,
             state_out,
             step,
             nullifier,
+            state_in_1,
+            nullifier_1,
+            redeemed,
+            now,
+            maturity,
+            expiry,
+            supply_in,
+            supply_out,
         })
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+// opening of a note that is spent by the sender
+pub struct NoteOpening<F: PrimeField> {
+    pub(crate) parent: BlindNoteHash<F>,
+    pub(crate) index: NoteOutIndex,
+    pub(crate) step: u32,
+    pub(crate) value: u64,
+    // merkle path to recover the state the note is created in
+    pub(crate) path: [BlindNoteHash<F>; STATE_DEPTH],
+    pub(crate) blind: Blind<F>,
+}
+
+impl<F: PrimeField> Default for NoteOpening<F> {
+    fn default() -> Self {
+        Self {
+            parent: Default::default(),
+            index: NoteOutIndex::FIRST_OUT,
+            step: 0,
+            value: 0,
+            path: Default::default(),
+            blind: Default::default(),
+        }
+    }
+}
+
+impl<F: PrimeField> NoteOpening<F> {
+    pub(crate) fn new(note: &Note<F>, path: &[BlindNoteHash<F>; STATE_DEPTH]) -> Self {
+        Self {
+            parent: note.parent_note,
+            index: note.out_index,
+            step: note.step,
+            value: note.value,
+            path: *path,
+            blind: note.blind,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+// output slot of a transaction, unused slots are zero leaves of the state tree
+pub struct NoteOutput<F: PrimeField> {
+    pub(crate) owner: Address<F>,
+    pub(crate) value: u64,
+    pub(crate) blind: Blind<F>,
+    pub(crate) used: bool,
+}
+
+impl<F: PrimeField> NoteOutput<F> {
+    pub(crate) fn new(note: &Note<F>) -> Self {
+        Self {
+            owner: note.owner,
+            value: note.value,
+            blind: note.blind,
+            used: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+// opening of the supply counter of the issuer, used by issue transactions only
+pub struct SupplyOpening<F: PrimeField> {
+    // value issued before the transaction
+    pub(crate) issued: u64,
+    pub(crate) blind_in: Blind<F>,
+    pub(crate) blind_out: Blind<F>,
+}
+
+impl<F: PrimeField> SupplyOpening<F> {
+    pub(crate) fn new(issued: u64, blind_in: &Blind<F>, blind_out: &Blind<F>) -> Self {
+        Self {
+            issued,
+            blind_in: *blind_in,
+            blind_out: *blind_out,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AuxInputs<E: IVC> {
-    // receiver address
-    pub(crate) receiver: Address<E::Field>,
+    // preimage of the asset hash
+    pub(crate) asset: Asset<E::Field>,
     // public key of the signer (sender or issuer)
     pub(crate) public_key: PublicKey<E::TE>,
     // signature of sender or issuer
     pub(crate) signature: Signature<E::TE>,
     // nullifier key of the sender. remember that we will use nullifier key of the "issuer" only for id commitment recovery
     pub(crate) nullifier_key: NullifierKey<E::Field>,
-    // asset hash defines context of the note tree
-    pub(crate) parent: BlindNoteHash<E::Field>,
-    // input index
-    pub(crate) input_index: NoteOutIndex,
-    // input value
-    pub(crate) value_in: u64,
-    // output value
-    pub(crate) value_out: u64,
-    // sibling note to recover the state
-    pub(crate) sibling: BlindNoteHash<E::Field>,
-    // input blind
-    pub(crate) blind_in: Blind<E::Field>,
-    // output blind of note 0
-    pub(crate) blind_out_0: Blind<E::Field>,
-    // output blind of note 1
-    pub(crate) blind_out_1: Blind<E::Field>,
+    // spent notes, split uses the first one and merge uses both
+    pub(crate) inputs: [NoteOpening<E::Field>; 2],
+    // outputs in position order
+    pub(crate) outputs: [NoteOutput<E::Field>; MAX_OUTPUTS],
+    // supply counter of the issuer
+    pub(crate) supply: SupplyOpening<E::Field>,
 }
 
 impl<E: IVC> AuxInputs<E> {
-    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
-        receiver: &Address<E::Field>,
+        asset: &Asset<E::Field>,
         public_key: &PublicKey<E::TE>,
         signature: &Signature<E::TE>,
         nullifier_key: &NullifierKey<E::Field>,
-        parent: &BlindNoteHash<E::Field>,
-        input_index: &NoteOutIndex,
-        value_in: u64,
-        value_out: u64,
-        sibling: &BlindNoteHash<E::Field>,
-        blind_in: &Blind<E::Field>,
-        blind_out_0: &Blind<E::Field>,
-        blind_out_1: &Blind<E::Field>,
     ) -> Self {
         Self {
-            receiver: *receiver,
+            asset: *asset,
             public_key: public_key.clone(),
             signature: signature.clone(),
             nullifier_key: *nullifier_key,
-            parent: *parent,
-            input_index: *input_index,
-            value_in,
-            value_out,
-            sibling: *sibling,
-            blind_in: *blind_in,
-            blind_out_0: *blind_out_0,
-            blind_out_1: *blind_out_1,
+            inputs: Default::default(),
+            outputs: [NoteOutput::default(); MAX_OUTPUTS],
+            supply: SupplyOpening::default(),
         }
     }
+
+    pub(crate) fn with_inputs(mut self, inputs: &[NoteOpening<E::Field>]) -> Self {
+        assert!(inputs.len() <= self.inputs.len());
+        self.inputs[..inputs.len()].copy_from_slice(inputs);
+        self
+    }
+
+    pub(crate) fn with_outputs(mut self, outputs: &[NoteOutput<E::Field>]) -> Self {
+        assert!(outputs.len() <= MAX_OUTPUTS);
+        self.outputs[..outputs.len()].copy_from_slice(outputs);
+        self
+    }
+
+    pub(crate) fn with_supply(mut self, supply: &SupplyOpening<E::Field>) -> Self {
+        self.supply = *supply;
+        self
+    }
 }
 
 #[derive(Clone, Debug)]
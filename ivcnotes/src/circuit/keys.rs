@@ -0,0 +1,390 @@
+use super::{Circuit, Prover, Verifier, IVC};
+use crate::poseidon::PoseidonConfigs;
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystem, OptimizationGoal, SynthesisError, SynthesisMode,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
+use digest::Digest;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: [u8; 4] = *b"IVCK";
+pub const KEY_FORMAT_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyFileError {
+    // file doesn't start with the magic bytes
+    NotAKeyFile,
+    // key file is written with another format version
+    UnsupportedVersion(u8),
+    // header has out of range fields
+    MalformedHeader,
+    // proving key is given where verifying key is expected or vice versa
+    UnexpectedKind,
+    // curve identifier doesn't fit into the header
+    CurveIdTooLong,
+    // key is generated for another curve
+    CurveMismatch { expected: String, found: String },
+    // key is generated with other poseidon parameters
+    PoseidonMismatch,
+    // key is generated for another version of the circuit
+    CircuitMismatch,
+}
+
+impl core::fmt::Display for KeyFileError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::NotAKeyFile => write!(f, "not a key file"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            Self::MalformedHeader => write!(f, "malformed header"),
+            Self::UnexpectedKind => write!(f, "unexpected key kind"),
+            Self::CurveIdTooLong => write!(f, "curve id is too long"),
+            Self::CurveMismatch { expected, found } => {
+                write!(f, "expected curve {}, found {}", expected, found)
+            }
+            Self::PoseidonMismatch => write!(f, "poseidon configs mismatch"),
+            Self::CircuitMismatch => write!(f, "circuit mismatch"),
+        }
+    }
+}
+
+impl ark_std::error::Error for KeyFileError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeyKind {
+    Proving,
+    Verifying,
+}
+
+impl From<KeyKind> for u8 {
+    fn from(kind: KeyKind) -> Self {
+        match kind {
+            KeyKind::Proving => 0,
+            KeyKind::Verifying => 1,
+        }
+    }
+}
+
+// digest of the circuit shape. keys from a different circuit version won't match
+pub fn circuit_fingerprint<E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+) -> Result<[u8; 32], crate::Error> {
+    let cs = ConstraintSystem::<E::Field>::new_ref();
+    cs.set_optimization_goal(OptimizationGoal::Constraints);
+    cs.set_mode(SynthesisMode::Setup);
+    Circuit::<E>::empty(h)
+        .generate_constraints(cs.clone())
+        .map_err(crate::Error::Synthesis)?;
+    cs.finalize();
+
+    let matrices = cs
+        .to_matrices()
+        .ok_or(crate::Error::Synthesis(SynthesisError::MissingCS))?;
+
+    let mut hasher = sha2::Sha256::new();
+    hasher.update((matrices.num_constraints as u64).to_le_bytes());
+    hasher.update((matrices.num_instance_variables as u64).to_le_bytes());
+    hasher.update((matrices.num_witness_variables as u64).to_le_bytes());
+    // every coefficient and its variable index, row by row
+    let mut coeff_bytes = vec![];
+    for matrix in [&matrices.a, &matrices.b, &matrices.c] {
+        for row in matrix.iter() {
+            hasher.update((row.len() as u64).to_le_bytes());
+            for (coeff, index) in row.iter() {
+                coeff_bytes.clear();
+                coeff.serialize_compressed(&mut coeff_bytes)?;
+                hasher.update(&coeff_bytes);
+                hasher.update((*index as u64).to_le_bytes());
+            }
+        }
+    }
+    Ok(hasher.finalize().into())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+// leading bytes of a key file
+pub struct KeyHeader {
+    // format version of the key file
+    pub(crate) version: u8,
+    // proving or verifying key
+    kind: u8,
+    // whether the key body is compressed
+    pub(crate) compress: bool,
+    // `IVC::CURVE_ID` of the instance keys are generated for
+    pub(crate) curve_id: String,
+    // `PoseidonConfigs::fingerprint` of hashers in use
+    pub(crate) poseidon: [u8; 32],
+    // `circuit_fingerprint` of the circuit keys are generated for
+    pub(crate) circuit: [u8; 32],
+}
+
+impl KeyHeader {
+    fn new<E: IVC>(
+        kind: KeyKind,
+        h: &PoseidonConfigs<E::Field>,
+        compress: Compress,
+    ) -> Result<Self, crate::Error> {
+        Ok(Self {
+            version: KEY_FORMAT_VERSION,
+            kind: kind.into(),
+            compress: compress == Compress::Yes,
+            curve_id: E::CURVE_ID.to_string(),
+            poseidon: h.fingerprint(),
+            circuit: circuit_fingerprint::<E>(h)?,
+        })
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> Result<(), crate::Error> {
+        let curve_id = self.curve_id.as_bytes();
+        let curve_id_len: u8 = curve_id
+            .len()
+            .try_into()
+            .map_err(|_err| KeyFileError::CurveIdTooLong)?;
+
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(self.version);
+        bytes.push(self.kind);
+        bytes.push(self.compress.into());
+        bytes.push(curve_id_len);
+        bytes.extend_from_slice(curve_id);
+        bytes.extend_from_slice(&self.poseidon);
+        bytes.extend_from_slice(&self.circuit);
+        Ok(writer.write_all(&bytes)?)
+    }
+
+    fn read<R: Read>(mut reader: R) -> Result<Self, crate::Error> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        (magic == MAGIC)
+            .then_some(())
+            .ok_or(KeyFileError::NotAKeyFile)?;
+
+        let mut fixed = [0u8; 4];
+        reader.read_exact(&mut fixed)?;
+        let [version, kind, compress, curve_id_len] = fixed;
+        (version == KEY_FORMAT_VERSION)
+            .then_some(())
+            .ok_or(KeyFileError::UnsupportedVersion(version))?;
+        let compress = match compress {
+            0 => false,
+            1 => true,
+            _ => return Err(KeyFileError::MalformedHeader.into()),
+        };
+
+        let mut curve_id = vec![0u8; curve_id_len as usize];
+        reader.read_exact(&mut curve_id)?;
+        let curve_id = String::from_utf8(curve_id).map_err(|_err| KeyFileError::MalformedHeader)?;
+
+        let mut poseidon = [0u8; 32];
+        reader.read_exact(&mut poseidon)?;
+        let mut circuit = [0u8; 32];
+        reader.read_exact(&mut circuit)?;
+
+        Ok(Self {
+            version,
+            kind,
+            compress,
+            curve_id,
+            poseidon,
+            circuit,
+        })
+    }
+
+    fn check<E: IVC>(
+        &self,
+        kind: KeyKind,
+        h: &PoseidonConfigs<E::Field>,
+    ) -> Result<(), crate::Error> {
+        (self.kind == u8::from(kind))
+            .then_some(())
+            .ok_or(KeyFileError::UnexpectedKind)?;
+        (self.curve_id == E::CURVE_ID)
+            .then_some(())
+            .ok_or_else(|| KeyFileError::CurveMismatch {
+                expected: E::CURVE_ID.to_string(),
+                found: self.curve_id.clone(),
+            })?;
+        (self.poseidon == h.fingerprint())
+            .then_some(())
+            .ok_or(KeyFileError::PoseidonMismatch)?;
+        (self.circuit == circuit_fingerprint::<E>(h)?)
+            .then_some(())
+            .ok_or(KeyFileError::CircuitMismatch.into())
+    }
+}
+
+fn write_key<E: IVC, K: CanonicalSerialize, W: Write>(
+    kind: KeyKind,
+    key: &K,
+    h: &PoseidonConfigs<E::Field>,
+    mut writer: W,
+    compress: Compress,
+) -> Result<(), crate::Error> {
+    KeyHeader::new::<E>(kind, h, compress)?.write(&mut writer)?;
+    Ok(key.serialize_with_mode(&mut writer, compress)?)
+}
+
+fn read_key<E: IVC, K: CanonicalDeserialize, R: Read>(
+    kind: KeyKind,
+    h: &PoseidonConfigs<E::Field>,
+    mut reader: R,
+) -> Result<K, crate::Error> {
+    let header = KeyHeader::read(&mut reader)?;
+    header.check::<E>(kind, h)?;
+    let compress = if header.compress {
+        Compress::Yes
+    } else {
+        Compress::No
+    };
+    Ok(K::deserialize_with_mode(
+        &mut reader,
+        compress,
+        Validate::Yes,
+    )?)
+}
+
+fn create(path: impl AsRef<Path>) -> Result<BufWriter<File>, crate::Error> {
+    Ok(BufWriter::new(File::create(path)?))
+}
+
+fn open(path: impl AsRef<Path>) -> Result<BufReader<File>, crate::Error> {
+    Ok(BufReader::new(File::open(path)?))
+}
+
+impl<E: IVC> Prover<E> {
+    pub fn write<W: Write>(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        writer: W,
+        compress: Compress,
+    ) -> Result<(), crate::Error> {
+        write_key::<E, _, _>(KeyKind::Proving, &self.pk, h, writer, compress)
+    }
+
+    pub fn read<R: Read>(h: &PoseidonConfigs<E::Field>, reader: R) -> Result<Self, crate::Error> {
+        let pk = read_key::<E, _, _>(KeyKind::Proving, h, reader)?;
+        Ok(Self { pk })
+    }
+
+    pub fn save(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        path: impl AsRef<Path>,
+        compress: Compress,
+    ) -> Result<(), crate::Error> {
+        let mut writer = create(path)?;
+        self.write(h, &mut writer, compress)?;
+        Ok(writer.flush()?)
+    }
+
+    pub fn load(
+        h: &PoseidonConfigs<E::Field>,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, crate::Error> {
+        Self::read(h, open(path)?)
+    }
+}
+
+impl<E: IVC> Verifier<E> {
+    pub fn write<W: Write>(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        writer: W,
+        compress: Compress,
+    ) -> Result<(), crate::Error> {
+        write_key::<E, _, _>(KeyKind::Verifying, &self.vk, h, writer, compress)
+    }
+
+    pub fn read<R: Read>(h: &PoseidonConfigs<E::Field>, reader: R) -> Result<Self, crate::Error> {
+        let vk = read_key::<E, _, _>(KeyKind::Verifying, h, reader)?;
+        Ok(Self { vk })
+    }
+
+    pub fn save(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        path: impl AsRef<Path>,
+        compress: Compress,
+    ) -> Result<(), crate::Error> {
+        let mut writer = create(path)?;
+        self.write(h, &mut writer, compress)?;
+        Ok(writer.flush()?)
+    }
+
+    pub fn load(
+        h: &PoseidonConfigs<E::Field>,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, crate::Error> {
+        Self::read(h, open(path)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::KeyFileError;
+    use crate::circuit::{Prover, Verifier};
+    use crate::poseidon::PoseidonConfigs;
+    use crate::test::Bls;
+    use ark_serialize::Compress;
+
+    fn read(h: &PoseidonConfigs<ark_bls12_381::Fr>, bytes: &[u8]) -> Option<KeyFileError> {
+        match Verifier::<Bls>::read(h, bytes) {
+            Ok(_) => None,
+            Err(crate::Error::KeyFile(err)) => Some(err),
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    // a header written for another curve, poseidon or circuit is rejected
+    #[test]
+    fn test_header_mismatch() {
+        let h = PoseidonConfigs::generate();
+        let mut bytes = vec![];
+        Verifier::<Bls> { vk: () }
+            .write(&h, &mut bytes, Compress::Yes)
+            .unwrap();
+        assert_eq!(read(&h, &bytes), None);
+        assert!(matches!(
+            Prover::<Bls>::read(&h, &bytes[..]),
+            Err(crate::Error::KeyFile(KeyFileError::UnexpectedKind))
+        ));
+
+        // magic, version, kind, compress and curve id length come first
+        let curve = 8;
+        let poseidon = curve + "bls12-381".len();
+        let circuit = poseidon + 32;
+
+        let mut tampered = bytes.clone();
+        tampered[poseidon - 1] = b'2';
+        assert_eq!(
+            read(&h, &tampered),
+            Some(KeyFileError::CurveMismatch {
+                expected: "bls12-381".to_string(),
+                found: "bls12-382".to_string(),
+            })
+        );
+
+        let mut tampered = bytes.clone();
+        tampered[poseidon] ^= 1;
+        assert_eq!(read(&h, &tampered), Some(KeyFileError::PoseidonMismatch));
+
+        let mut tampered = bytes.clone();
+        tampered[circuit] ^= 1;
+        assert_eq!(read(&h, &tampered), Some(KeyFileError::CircuitMismatch));
+
+        let mut tampered = bytes.clone();
+        tampered[4] += 1;
+        assert_eq!(
+            read(&h, &tampered),
+            Some(KeyFileError::UnsupportedVersion(2))
+        );
+
+        let mut tampered = bytes;
+        tampered[0] ^= 1;
+        assert_eq!(read(&h, &tampered), Some(KeyFileError::NotAKeyFile));
+    }
+}
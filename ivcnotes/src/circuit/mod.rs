@@ -19,10 +19,14 @@ use ark_relations::r1cs::{
 };
 use cs::synth;
 use inputs::{AuxInputs, PublicInput};
-use rand::{CryptoRng, RngCore};
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 
+#[cfg(feature = "groth16")]
+pub mod batch;
 pub mod cs;
 pub mod inputs;
+pub mod keys;
 
 fn verify_signature<F: PrimeField, TE: TECurveConfig<BaseField = F>>(
     cs: impl Into<Namespace<F>>,
@@ -59,6 +63,39 @@ pub trait IVC: Clone {
     type Field: PrimeField + Absorb;
     // inner curve - (baby)jubjub config
     type TE: TECurveConfig<BaseField = Self::Field> + Clone;
+    // curve identifier, tags serialized keys
+    const CURVE_ID: &'static str;
+
+    // checks proofs against their public inputs at once. proof systems without a
+    // batch check verify one by one, `batch::Groth16IVC` uses `batch::groth16`
+    #[allow(clippy::type_complexity)]
+    fn verify_batch<R: RngCore + CryptoRng>(
+        vk: &<Self::Snark as SNARK<Self::Field>>::VerifyingKey,
+        instances: &[(
+            Vec<Self::Field>,
+            &<Self::Snark as SNARK<Self::Field>>::Proof,
+        )],
+        _rng: &mut R,
+    ) -> Result<bool, <Self::Snark as SNARK<Self::Field>>::Error> {
+        // snark errors are not sendable, so parallel checks count them as failures
+        // and leave reporting to the per step fallback of `Verifier::verify_batch`
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            Ok(instances
+                .par_iter()
+                .all(|(input, proof)| matches!(Self::Snark::verify(vk, input, proof), Ok(true))))
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            for (input, proof) in instances {
+                if !Self::Snark::verify(vk, input, proof)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+    }
 }
 
 pub struct Circuit<'a, E: IVC> {
@@ -103,6 +140,26 @@ pub struct Verifier<E: IVC> {
     pub(crate) vk: <<E as IVC>::Snark as SNARK<E::Field>>::VerifyingKey,
 }
 
+// run circuit specific setup against the empty circuit and return the matching key pair
+pub fn setup<E: IVC, R: RngCore + CryptoRng>(
+    h: &PoseidonConfigs<E::Field>,
+    rng: &mut R,
+) -> Result<(Prover<E>, Verifier<E>), crate::Error> {
+    let circuit = Circuit::<E>::empty(h);
+    let (pk, vk) = <E as IVC>::Snark::circuit_specific_setup(circuit, rng)
+        .map_err(|err| crate::Error::Setup(Box::new(err)))?;
+    Ok((Prover { pk }, Verifier { vk }))
+}
+
+// deterministic setup. parties that share the seed end up with identical keys
+pub fn setup_from_seed<E: IVC>(
+    h: &PoseidonConfigs<E::Field>,
+    seed: [u8; 32],
+) -> Result<(Prover<E>, Verifier<E>), crate::Error> {
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    setup(h, &mut rng)
+}
+
 impl<E: IVC> Prover<E> {
     pub fn create_proof<R: RngCore + CryptoRng>(
         &self,
@@ -113,7 +170,7 @@ impl<E: IVC> Prover<E> {
     ) -> Result<<<E as IVC>::Snark as SNARK<E::Field>>::Proof, crate::Error> {
         let circuit = Circuit::new(h, public, aux);
         <E as IVC>::Snark::prove(&self.pk, circuit, rng)
-            .map_err(|_err| crate::Error::With("proof generation failed"))
+            .map_err(|err| crate::Error::ProofGeneration(Box::new(err)))
     }
 }
 
@@ -124,7 +181,110 @@ impl<E: IVC> Verifier<E> {
         pi: &PublicInput<E::Field>,
     ) -> Result<bool, crate::Error> {
         let pi = pi.to_verifier();
-        E::Snark::verify(&self.vk, &pi, proof)
-            .map_err(|_err| crate::Error::With("verification failed"))
+        E::Snark::verify(&self.vk, &pi, proof).map_err(|err| crate::Error::Verifier(Box::new(err)))
+    }
+
+    // verifies all proofs with a single batch check. if the batch fails proofs are
+    // checked one by one to find the first failing one, `None` if all verify
+    #[allow(clippy::type_complexity)]
+    pub fn verify_batch<R: RngCore + CryptoRng>(
+        &self,
+        steps: &[(
+            &<<E as IVC>::Snark as SNARK<E::Field>>::Proof,
+            &PublicInput<E::Field>,
+        )],
+        rng: &mut R,
+    ) -> Result<Option<usize>, crate::Error> {
+        let instances = steps
+            .iter()
+            .map(|(proof, pi)| (pi.to_verifier(), *proof))
+            .collect::<Vec<_>>();
+        let verified = E::verify_batch(&self.vk, &instances, rng)
+            .map_err(|err| crate::Error::Verifier(Box::new(err)))?;
+        if verified {
+            return Ok(None);
+        }
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            let failing = steps
+                .par_iter()
+                .position_first(|(proof, pi)| !matches!(self.verify_proof(proof, pi), Ok(true)));
+            match failing {
+                Some(i) => {
+                    // surfaces the error if the step did not fail cleanly
+                    let (proof, pi) = steps[i];
+                    self.verify_proof(proof, pi)?;
+                    Ok(Some(i))
+                }
+                None => Ok(None),
+            }
+        }
+        #[cfg(not(feature = "parallel"))]
+        {
+            for (i, (proof, pi)) in steps.iter().enumerate() {
+                if !self.verify_proof(proof, pi)? {
+                    return Ok(Some(i));
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::inputs::PublicInput;
+    use super::Verifier;
+    use crate::test::Bls;
+    use crate::FWrap;
+    use ark_bls12_381::Fr;
+    use rand::SeedableRng;
+
+    // same answers with and without the `parallel` feature
+    #[test]
+    fn test_verify_batch() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let verifier = Verifier::<Bls> { vk: () };
+        let inputs = (0..16u32)
+            .map(|step| {
+                PublicInput::new(
+                    &FWrap::rand(&mut rng),
+                    &FWrap::rand(&mut rng),
+                    &FWrap::rand(&mut rng),
+                    step,
+                    &FWrap::rand(&mut rng),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut proofs = inputs
+            .iter()
+            .map(|input| input.to_verifier())
+            .collect::<Vec<_>>();
+        let steps = |proofs: &[Vec<Fr>]| {
+            proofs
+                .iter()
+                .cloned()
+                .zip(inputs.iter())
+                .collect::<Vec<_>>()
+        };
+        let check = |proofs: &[Vec<Fr>], rng: &mut rand_chacha::ChaCha20Rng| {
+            let steps = steps(proofs);
+            let steps = steps
+                .iter()
+                .map(|(proof, pi)| (proof, *pi))
+                .collect::<Vec<_>>();
+            let sequential = steps
+                .iter()
+                .position(|(proof, pi)| !verifier.verify_proof(proof, pi).unwrap());
+            assert_eq!(verifier.verify_batch(&steps, rng).unwrap(), sequential);
+            sequential
+        };
+        assert_eq!(check(&proofs, &mut rng), None);
+
+        // the first failing step is reported however many fail
+        proofs[11] = proofs[10].clone();
+        proofs[5] = proofs[4].clone();
+        assert_eq!(check(&proofs, &mut rng), Some(5));
     }
 }
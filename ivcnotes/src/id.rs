@@ -1,13 +1,25 @@
 use crate::{circuit::IVC, poseidon::PoseidonConfigs, Address, FWrap, NullifierKey, SigHash};
-use ark_crypto_primitives::{sponge::poseidon::PoseidonConfig, Error};
-use arkeddsa::{signature::Signature, PublicKey, SigningKey};
+use ark_crypto_primitives::{
+    sponge::{
+        poseidon::{PoseidonConfig, PoseidonSponge},
+        CryptographicSponge,
+    },
+    Error,
+};
+use ark_ec::{twisted_edwards::Affine, AffineRepr, CurveGroup};
+use arkeddsa::{signature::Signature, PublicKey, SecretKey, SigningKey};
 use rand_core::CryptoRngCore;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 type PreHash = sha2::Sha512;
 
-#[derive(Debug)]
+#[derive(Debug, Zeroize, ZeroizeOnDrop)]
 // Signer has the signer key and eddsa poseidon config
 pub struct Signer<E: IVC> {
+    // secret seed that the signing key is derived from
+    seed: [u8; 32],
+    #[zeroize(skip)]
     signing_key: SigningKey<E::TE>,
+    #[zeroize(skip)]
     poseidon: PoseidonConfig<E::Field>,
 }
 
@@ -16,13 +28,25 @@ impl<E: IVC> Signer<E> {
         poseidon: &PoseidonConfig<E::Field>,
         rng: &mut impl CryptoRngCore,
     ) -> Self {
-        let signing_key = SigningKey::generate::<PreHash>(rng).unwrap();
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        Self::from_seed(poseidon, seed)
+    }
+
+    // the seed is the eddsa secret key itself
+    pub(crate) fn from_seed(poseidon: &PoseidonConfig<E::Field>, seed: [u8; 32]) -> Self {
+        let signing_key = SigningKey::new::<PreHash>(&SecretKey(seed)).unwrap();
         Self {
+            seed,
             signing_key,
             poseidon: poseidon.clone(),
         }
     }
 
+    pub(crate) fn seed(&self) -> &[u8; 32] {
+        &self.seed
+    }
+
     pub(crate) fn sign(&self, msg: &E::Field) -> Signature<E::TE> {
         self.signing_key.sign::<PreHash, _>(&self.poseidon, &[*msg])
     }
@@ -32,6 +56,22 @@ impl<E: IVC> Signer<E> {
     }
 }
 
+// native counterpart of the signature check in the circuit
+pub(crate) fn verify_signature<E: IVC>(
+    poseidon: &PoseidonConfig<E::Field>,
+    public_key: &PublicKey<E::TE>,
+    signature: &Signature<E::TE>,
+    msg: &SigHash<E::Field>,
+) -> bool {
+    let mut sponge = PoseidonSponge::new(poseidon);
+    sponge.absorb(signature.r());
+    sponge.absorb(public_key.as_ref());
+    sponge.absorb(&msg.inner());
+    let k = sponge.squeeze_field_elements::<<E::TE as ark_ec::CurveConfig>::ScalarField>(1)[0];
+    let s_b = Affine::<E::TE>::generator() * signature.s();
+    (s_b - *public_key.as_ref() * k).into_affine() == *signature.r()
+}
+
 // `Id` holds user secrets and public address
 pub struct Auth<E: IVC> {
     nullifier_key: NullifierKey<E::Field>,
@@ -46,18 +86,44 @@ impl<E: IVC> Auth<E> {
     ) -> Result<Self, Error> {
         let signer = Signer::generate(&h.eddsa, rng);
         let nullifier_key = NullifierKey::rand(rng);
+        Ok(Self::new(h, nullifier_key, signer))
+    }
+
+    // restore from secrets
+    pub(crate) fn from_secrets(
+        h: &PoseidonConfigs<E::Field>,
+        nullifier_key: NullifierKey<E::Field>,
+        signer_seed: [u8; 32],
+    ) -> Self {
+        let signer = Signer::from_seed(&h.eddsa, signer_seed);
+        Self::new(h, nullifier_key, signer)
+    }
+
+    fn new(
+        h: &PoseidonConfigs<E::Field>,
+        nullifier_key: NullifierKey<E::Field>,
+        signer: Signer<E>,
+    ) -> Self {
         let address = h.id_commitment(&nullifier_key, signer.public_key());
-        Ok(Self {
+        Self {
             nullifier_key,
             signer,
             address,
-        })
+        }
+    }
+
+    pub(crate) fn signer_seed(&self) -> &[u8; 32] {
+        self.signer.seed()
     }
 
     pub(crate) fn address(&self) -> &Address<E::Field> {
         &self.address
     }
 
+    pub(crate) fn key_commitment(&self, h: &PoseidonConfigs<E::Field>) -> E::Field {
+        h.key_commitment(&self.nullifier_key)
+    }
+
     pub(crate) fn nullifier_key(&self) -> &NullifierKey<E::Field> {
         &self.nullifier_key
     }
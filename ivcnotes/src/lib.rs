@@ -1,12 +1,17 @@
 use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, SerializationError};
 use std::borrow::Borrow;
 
+pub mod address;
 pub mod asset;
 pub mod circuit;
 // pub mod cs;
 pub mod id;
 pub mod note;
 pub mod poseidon;
+pub mod seed;
+#[cfg(test)]
+mod test;
 pub mod tx;
 pub mod wallet;
 
@@ -19,21 +24,189 @@ crate::field_wrap!(AssetHash);
 crate::field_wrap!(Blind);
 crate::field_wrap!(NoteHash);
 crate::field_wrap!(BlindNoteHash);
+crate::field_wrap!(SupplyHash);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug)]
 pub enum Error {
-    With(&'static str),
+    // note history is not addressed to the receiver
+    NotRecipient,
+    // unspent note and its merkle path don't open to the last state
+    BadCurrentState,
+    // proof of the step in the note history is rejected
+    VerificationFailed { step: note::StepPath },
+    // mnemonic phrase has unknown words or bad checksum
+    BadMnemonic,
+    // spendable note doesn't cover the value to send
+    InsufficientFunds { available: u64, requested: u64 },
+    // no spendable note at the index
+    BadSpendableIndex(usize),
+    // no account at the index
+    BadAccountIndex(usize),
+    // notes of different assets cannot be merged
+    AssetMismatch,
+    // merge would nest merged inputs deeper than `note::MAX_MERGE_DEPTH`
+    MergeTooDeep,
+    // merged input shares more steps than precede the merge step
+    BadMergedInput { step: note::StepPath },
+    // value sum doesn't fit into u64
+    ValueOverflow,
+    // split has more receivers than the free output slots
+    TooManyOutputs(usize),
+    // output position is not below `tx::MAX_OUTPUTS`
+    BadOutIndex(usize),
+    // asset is not issued by any account of the wallet
+    NotIssuer,
+    // bond without a coupon period or starting after maturity
+    BadTerms,
+    // note is already redeemed
+    AlreadyRedeemed,
+    // redemption receipt is not signed by the issuer or doesn't match the redemption
+    BadReceipt,
+    // nullifier is already spent by another step
+    DoubleSpend,
+    // issuance doesn't extend the published supply chain of the asset
+    SupplyConflict,
+    // redemption exceeds the value in circulation
+    ExceedsSupply { outstanding: u64, requested: u64 },
+    // issuance would go over the supply cap of the asset
+    ExceedsCap { cap: u64, requested: u64 },
+    // note cannot be redeemed before maturity
+    NotMatured { maturity: u64 },
+    // note cannot be transferred after expiry
+    Expired { expiry: u64 },
+    // receiver is not the merchant of the voucher
+    MerchantRestricted,
+    // step time goes backwards, is in the future or too far in the past
+    BadTimestamp { step: note::StepPath },
+    // snark failed to generate the proof
+    ProofGeneration(Box<dyn ark_std::error::Error>),
+    // snark failed to run the verifier
+    Verifier(Box<dyn ark_std::error::Error>),
+    // snark failed to generate the keys
+    Setup(Box<dyn ark_std::error::Error>),
+    // circuit synthesis failed outside of the snark
+    Synthesis(ark_relations::r1cs::SynthesisError),
+    // key file is malformed or doesn't match the configuration in use
+    KeyFile(circuit::keys::KeyFileError),
+    // wallet file is malformed or cannot be decrypted
+    WalletFile(wallet::file::WalletFileError),
+    Serialization(SerializationError),
+    Io(std::io::Error),
 }
 
 impl core::fmt::Display for Error {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        match *self {
-            Self::With(s) => write!(f, "{}", s),
+        match self {
+            Self::NotRecipient => write!(f, "not the recipient"),
+            Self::BadCurrentState => write!(f, "bad current state"),
+            Self::VerificationFailed { step } => {
+                write!(f, "proof verification failed at step {}", step)
+            }
+            Self::InsufficientFunds {
+                available,
+                requested,
+            } => write!(
+                f,
+                "insufficient funds, available {} requested {}",
+                available, requested
+            ),
+            Self::BadMnemonic => write!(f, "bad mnemonic"),
+            Self::BadSpendableIndex(index) => write!(f, "bad spendable index {}", index),
+            Self::BadAccountIndex(index) => write!(f, "bad account index {}", index),
+            Self::AssetMismatch => write!(f, "asset mismatch"),
+            Self::MergeTooDeep => write!(f, "merge nesting too deep"),
+            Self::BadMergedInput { step } => write!(f, "bad merged input at step {}", step),
+            Self::ValueOverflow => write!(f, "value overflow"),
+            Self::TooManyOutputs(n) => write!(f, "too many outputs {}", n),
+            Self::BadOutIndex(position) => write!(f, "bad output index {}", position),
+            Self::NotIssuer => write!(f, "not the issuer"),
+            Self::NotMatured { maturity } => write!(f, "not matured until {}", maturity),
+            Self::Expired { expiry } => write!(f, "expired at {}", expiry),
+            Self::MerchantRestricted => write!(f, "receiver is not the merchant"),
+            Self::BadTimestamp { step } => write!(f, "bad timestamp at step {}", step),
+            Self::BadTerms => write!(f, "bad asset terms"),
+            Self::AlreadyRedeemed => write!(f, "already redeemed"),
+            Self::BadReceipt => write!(f, "bad redemption receipt"),
+            Self::DoubleSpend => write!(f, "double spend"),
+            Self::SupplyConflict => write!(f, "supply chain conflict"),
+            Self::ExceedsSupply {
+                outstanding,
+                requested,
+            } => write!(
+                f,
+                "exceeds supply, outstanding {} requested {}",
+                outstanding, requested
+            ),
+            Self::ExceedsCap { cap, requested } => {
+                write!(f, "exceeds supply cap {}, requested {}", cap, requested)
+            }
+            Self::ProofGeneration(err) => write!(f, "proof generation failed: {}", err),
+            Self::Verifier(err) => write!(f, "verifier failed: {}", err),
+            Self::Setup(err) => write!(f, "setup failed: {}", err),
+            Self::Synthesis(err) => write!(f, "synthesis failed: {}", err),
+            Self::KeyFile(err) => write!(f, "key file: {}", err),
+            Self::WalletFile(err) => write!(f, "wallet file: {}", err),
+            Self::Serialization(err) => write!(f, "serialization failed: {}", err),
+            Self::Io(err) => write!(f, "io: {}", err),
         }
     }
 }
 
-impl ark_std::error::Error for Error {}
+impl ark_std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn ark_std::error::Error + 'static)> {
+        match self {
+            Self::ProofGeneration(err) | Self::Verifier(err) | Self::Setup(err) => {
+                Some(err.as_ref())
+            }
+            Self::Synthesis(err) => Some(err),
+            Self::KeyFile(err) => Some(err),
+            Self::WalletFile(err) => Some(err),
+            Self::Serialization(err) => Some(err),
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<SerializationError> for Error {
+    fn from(err: SerializationError) -> Self {
+        Self::Serialization(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<circuit::keys::KeyFileError> for Error {
+    fn from(err: circuit::keys::KeyFileError) -> Self {
+        Self::KeyFile(err)
+    }
+}
+
+// version tag leading the canonical encoding of note histories and of every
+// composite item nested in them, so that each can be read on its own. bumped
+// between releases only
+pub const ENCODING_VERSION: u8 = 1;
+
+pub(crate) fn serialize_version<W: ark_serialize::Write>(
+    writer: W,
+    compress: Compress,
+) -> Result<(), SerializationError> {
+    ENCODING_VERSION.serialize_with_mode(writer, compress)
+}
+
+pub(crate) fn deserialize_version<R: ark_serialize::Read>(
+    reader: R,
+    compress: Compress,
+) -> Result<(), SerializationError> {
+    let version = u8::deserialize_with_mode(reader, compress, ark_serialize::Validate::No)?;
+    (version == ENCODING_VERSION)
+        .then_some(())
+        .ok_or(SerializationError::InvalidData)
+}
 
 pub trait FWrap<F: ark_ff::PrimeField>: From<F> + AsRef<F> {
     fn inner(&self) -> F;
@@ -98,5 +271,41 @@ macro_rules! field_wrap {
                 &self.0
             }
         }
+
+        impl<F: ark_ff::PrimeField> ark_serialize::CanonicalSerialize for $name<F> {
+            fn serialize_with_mode<W: ark_serialize::Write>(
+                &self,
+                writer: W,
+                compress: ark_serialize::Compress,
+            ) -> Result<(), ark_serialize::SerializationError> {
+                self.0.serialize_with_mode(writer, compress)
+            }
+
+            fn serialized_size(&self, compress: ark_serialize::Compress) -> usize {
+                self.0.serialized_size(compress)
+            }
+        }
+
+        impl<F: ark_ff::PrimeField> ark_serialize::Valid for $name<F> {
+            fn check(&self) -> Result<(), ark_serialize::SerializationError> {
+                self.0.check()
+            }
+        }
+
+        impl<F: ark_ff::PrimeField> ark_serialize::CanonicalDeserialize for $name<F> {
+            fn deserialize_with_mode<R: ark_serialize::Read>(
+                reader: R,
+                compress: ark_serialize::Compress,
+                validate: ark_serialize::Validate,
+            ) -> Result<Self, ark_serialize::SerializationError> {
+                F::deserialize_with_mode(reader, compress, validate).map(Self)
+            }
+        }
     };
 }
+
+impl From<wallet::file::WalletFileError> for Error {
+    fn from(err: wallet::file::WalletFileError) -> Self {
+        Self::WalletFile(err)
+    }
+}
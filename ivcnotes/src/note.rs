@@ -1,37 +1,117 @@
 use crate::{
-    asset::Asset, circuit::IVC, poseidon::PoseidonConfigs, tx::IssueTx, Address, AssetHash, Blind,
-    BlindNoteHash, Nullifier, StateHash,
+    asset::Asset,
+    circuit::IVC,
+    deserialize_version,
+    poseidon::{PoseidonConfigs, STATE_DEPTH},
+    serialize_version,
+    tx::{IssueTx, MAX_OUTPUTS},
+    Address, AssetHash, Blind, BlindNoteHash, Nullifier, StateHash, SupplyHash,
 };
 use ark_crypto_primitives::{snark::SNARK, sponge::Absorb};
 use ark_ff::PrimeField;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
+};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum NoteOutIndex {
     // Original note hash the issue tag
     Issue,
-    // Output with index 0 conventionally this is the refund note
-    Out0,
-    // Output with index 1 conventionally this is the sent note
-    Out1,
+    // Output at the position. conventionally output 0 is the refund note and
+    // the rest are sent notes
+    Out(OutPosition),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+// output position, less than `MAX_OUTPUTS`
+pub struct OutPosition(u8);
+
+impl OutPosition {
+    pub fn new(position: usize) -> Result<Self, crate::Error> {
+        (position < MAX_OUTPUTS)
+            .then_some(OutPosition(position as u8))
+            .ok_or(crate::Error::BadOutIndex(position))
+    }
+
+    pub fn get(&self) -> usize {
+        self.0 as usize
+    }
 }
 
 impl NoteOutIndex {
+    // first output of a split or the output of a merge
+    pub const FIRST_OUT: Self = NoteOutIndex::Out(OutPosition(0));
+
+    pub fn out(position: usize) -> Result<Self, crate::Error> {
+        OutPosition::new(position).map(NoteOutIndex::Out)
+    }
+
     pub(crate) fn inner<F: ark_ff::Field>(&self) -> F {
         let u: u8 = self.into();
         u.into()
     }
+
+    // leaf position in the state tree, issued note is the first leaf
+    pub fn position(&self) -> usize {
+        match self {
+            NoteOutIndex::Issue => 0,
+            NoteOutIndex::Out(position) => position.get(),
+        }
+    }
 }
 
 impl From<&NoteOutIndex> for u8 {
     fn from(val: &NoteOutIndex) -> Self {
         match val {
             NoteOutIndex::Issue => 0,
-            NoteOutIndex::Out0 => 1,
-            NoteOutIndex::Out1 => 2,
+            NoteOutIndex::Out(position) => position.0 + 1,
+        }
+    }
+}
+
+impl TryFrom<u8> for NoteOutIndex {
+    type Error = SerializationError;
+
+    fn try_from(val: u8) -> Result<Self, Self::Error> {
+        match val {
+            0 => Ok(NoteOutIndex::Issue),
+            val if (val as usize) <= MAX_OUTPUTS => Ok(NoteOutIndex::Out(OutPosition(val - 1))),
+            _ => Err(SerializationError::InvalidData),
         }
     }
 }
 
+impl CanonicalSerialize for NoteOutIndex {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        u8::from(self).serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, _compress: Compress) -> usize {
+        1
+    }
+}
+
+impl Valid for NoteOutIndex {
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl CanonicalDeserialize for NoteOutIndex {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        u8::deserialize_with_mode(&mut reader, compress, validate)?.try_into()
+    }
+}
+
 #[derive(Clone, Debug, Copy)]
 pub struct Note<F: PrimeField> {
     // asset hash defines context of the note tree
@@ -72,16 +152,180 @@ impl<F: PrimeField + Absorb> Note<F> {
     }
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+// position of a step in a note history, its index followed by the indexes in the
+// merged chains entered on the way. [4, 1] is step 1 of the chain merged at step 4
+pub struct StepPath(pub(crate) Vec<usize>);
+
+impl StepPath {
+    pub(crate) fn at(index: usize) -> Self {
+        Self(vec![index])
+    }
+
+    // step of the chain merged at this step
+    pub(crate) fn merged(&self, index: usize) -> Self {
+        let mut path = self.0.clone();
+        path.push(index);
+        Self(path)
+    }
+
+    pub fn indexes(&self) -> &[usize] {
+        &self.0
+    }
+}
+
+impl core::fmt::Display for StepPath {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let indexes = self.0.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+        write!(f, "{}", indexes.join("/"))
+    }
+}
+
 #[derive(Clone)]
-// part of intermediate public inputs
+// part of intermediate public inputs. senders are circuit witnesses and never leave
+// the prover. states and nullifiers stay public since every step is verified on its
+// own and receivers link spends through the nullifier registry
 pub struct IVCStep<E: IVC> {
     pub(crate) proof: <<E as IVC>::Snark as SNARK<E::Field>>::Proof,
     // output state hash
     pub(crate) state: StateHash<E::Field>,
     // nullifier of spent note
     pub(crate) nullifier: Nullifier<E::Field>,
-    // previous owner, signer of the input note or issuer
-    pub(crate) sender: Address<E::Field>,
+    // unix time the step claims to be proven at
+    pub(crate) time: u64,
+    // second input if the step is a merge
+    pub(crate) merged: Option<MergedInput<E>>,
+    // supply commitments if the step is an issuance
+    pub(crate) issuance: Option<Issuance<E::Field>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+// supply counter commitments of the issuer before and after an issue step
+pub struct Issuance<F: PrimeField> {
+    pub(crate) supply_in: SupplyHash<F>,
+    pub(crate) supply_out: SupplyHash<F>,
+}
+
+impl<F: PrimeField> Issuance<F> {
+    pub fn new(supply_in: &SupplyHash<F>, supply_out: &SupplyHash<F>) -> Self {
+        Issuance {
+            supply_in: *supply_in,
+            supply_out: *supply_out,
+        }
+    }
+
+    pub fn supply_in(&self) -> &SupplyHash<F> {
+        &self.supply_in
+    }
+
+    pub fn supply_out(&self) -> &SupplyHash<F> {
+        &self.supply_out
+    }
+}
+
+// merged inputs nest step vectors, nesting is bounded so that decoding and
+// verification don't recurse without limit
+pub const MAX_MERGE_DEPTH: usize = 16;
+
+#[derive(Clone)]
+// history of the second note spent by a merge step. steps shared with the
+// history of the first note are kept once, in the first history
+pub struct MergedInput<E: IVC> {
+    // nullifier of the second note
+    pub(crate) nullifier: Nullifier<E::Field>,
+    // number of leading steps the second history shares with the first one
+    pub(crate) shared: u32,
+    // rest of the steps up to the second note
+    pub(crate) steps: Vec<IVCStep<E>>,
+}
+
+// levels of merged inputs nested in the steps
+pub(crate) fn merge_depth<E: IVC>(steps: &[IVCStep<E>]) -> usize {
+    steps
+        .iter()
+        .filter_map(|step| step.merged.as_ref())
+        .map(|merged| 1 + merge_depth(&merged.steps))
+        .max()
+        .unwrap_or(0)
+}
+
+// decodes a step vector at the nesting level `depth`. steps are not validated
+// here, the item that is read is checked once as a whole
+fn deserialize_steps<E: IVC>(
+    reader: &mut dyn Read,
+    compress: Compress,
+    depth: usize,
+) -> Result<Vec<IVCStep<E>>, SerializationError> {
+    if depth > MAX_MERGE_DEPTH {
+        return Err(SerializationError::InvalidData);
+    }
+    let len = u64::deserialize_with_mode(&mut *reader, compress, Validate::No)?;
+    (0..len)
+        .map(|_| IVCStep::deserialize_at(&mut *reader, compress, depth))
+        .collect()
+}
+
+impl<F: PrimeField> CanonicalSerialize for Note<F> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_version(&mut writer, compress)?;
+        self.asset_hash.serialize_with_mode(&mut writer, compress)?;
+        self.owner.serialize_with_mode(&mut writer, compress)?;
+        self.value.serialize_with_mode(&mut writer, compress)?;
+        self.step.serialize_with_mode(&mut writer, compress)?;
+        self.parent_note
+            .serialize_with_mode(&mut writer, compress)?;
+        self.out_index.serialize_with_mode(&mut writer, compress)?;
+        self.blind.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        1 + self.asset_hash.serialized_size(compress)
+            + self.owner.serialized_size(compress)
+            + self.value.serialized_size(compress)
+            + self.step.serialized_size(compress)
+            + self.parent_note.serialized_size(compress)
+            + self.out_index.serialized_size(compress)
+            + self.blind.serialized_size(compress)
+    }
+}
+
+impl<F: PrimeField> Valid for Note<F> {
+    fn check(&self) -> Result<(), SerializationError> {
+        // issued notes are the roots of note trees
+        if self.out_index == NoteOutIndex::Issue
+            && (self.step != 0 || self.parent_note != BlindNoteHash::default())
+        {
+            return Err(SerializationError::InvalidData);
+        }
+        Ok(())
+    }
+}
+
+impl<F: PrimeField> CanonicalDeserialize for Note<F> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        deserialize_version(&mut reader, compress)?;
+        let note = Note {
+            asset_hash: AssetHash::deserialize_with_mode(&mut reader, compress, validate)?,
+            owner: Address::deserialize_with_mode(&mut reader, compress, validate)?,
+            value: u64::deserialize_with_mode(&mut reader, compress, validate)?,
+            step: u32::deserialize_with_mode(&mut reader, compress, validate)?,
+            parent_note: BlindNoteHash::deserialize_with_mode(&mut reader, compress, validate)?,
+            out_index: NoteOutIndex::deserialize_with_mode(&mut reader, compress, validate)?,
+            blind: Blind::deserialize_with_mode(&mut reader, compress, validate)?,
+        };
+        if validate == Validate::Yes {
+            note.check()?;
+        }
+        Ok(note)
+    }
 }
 
 impl<E: IVC> std::fmt::Debug for IVCStep<E> {
@@ -89,25 +333,250 @@ impl<E: IVC> std::fmt::Debug for IVCStep<E> {
         f.debug_struct("IVCStep")
             .field("state", &self.state)
             .field("nullifier", &self.nullifier)
-            .field("sender", &self.sender)
+            .field("time", &self.time)
+            .field("merged", &self.merged)
+            .field("issuance", &self.issuance)
+            .finish()
+    }
+}
+
+impl<E: IVC> std::fmt::Debug for MergedInput<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MergedInput")
+            .field("nullifier", &self.nullifier)
+            .field("steps", &self.steps)
             .finish()
     }
 }
 
+impl<F: PrimeField> CanonicalSerialize for Issuance<F> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_version(&mut writer, compress)?;
+        self.supply_in.serialize_with_mode(&mut writer, compress)?;
+        self.supply_out.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        1 + self.supply_in.serialized_size(compress) + self.supply_out.serialized_size(compress)
+    }
+}
+
+impl<F: PrimeField> Valid for Issuance<F> {
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl<F: PrimeField> CanonicalDeserialize for Issuance<F> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        deserialize_version(&mut reader, compress)?;
+        Ok(Issuance {
+            supply_in: SupplyHash::deserialize_with_mode(&mut reader, compress, validate)?,
+            supply_out: SupplyHash::deserialize_with_mode(&mut reader, compress, validate)?,
+        })
+    }
+}
+
+impl<E: IVC> MergedInput<E> {
+    // `steps` of the second history, leading steps in common with `host` are
+    // not copied
+    pub fn new(nullifier: &Nullifier<E::Field>, host: &[IVCStep<E>], steps: &[IVCStep<E>]) -> Self {
+        let shared = host
+            .iter()
+            .zip(steps.iter())
+            .take_while(|(a, b)| a.state == b.state && a.nullifier == b.nullifier)
+            .count();
+        MergedInput {
+            nullifier: *nullifier,
+            shared: shared as u32,
+            steps: steps[shared..].to_vec(),
+        }
+    }
+
+    pub fn shared(&self) -> usize {
+        self.shared as usize
+    }
+
+    fn deserialize_at(
+        reader: &mut dyn Read,
+        compress: Compress,
+        depth: usize,
+    ) -> Result<Self, SerializationError> {
+        deserialize_version(&mut *reader, compress)?;
+        Ok(MergedInput {
+            nullifier: Nullifier::deserialize_with_mode(&mut *reader, compress, Validate::No)?,
+            shared: u32::deserialize_with_mode(&mut *reader, compress, Validate::No)?,
+            steps: deserialize_steps(reader, compress, depth)?,
+        })
+    }
+}
+
+impl<E: IVC> CanonicalSerialize for MergedInput<E> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        // steps nest merged inputs again, erase the writer type to keep instantiation finite
+        let writer: &mut dyn Write = &mut writer;
+        serialize_version(&mut *writer, compress)?;
+        self.nullifier.serialize_with_mode(&mut *writer, compress)?;
+        self.shared.serialize_with_mode(&mut *writer, compress)?;
+        self.steps.serialize_with_mode(writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        1 + self.nullifier.serialized_size(compress)
+            + self.shared.serialized_size(compress)
+            + self.steps.serialized_size(compress)
+    }
+}
+
+impl<E: IVC> Valid for MergedInput<E> {
+    fn check(&self) -> Result<(), SerializationError> {
+        // second note differs from the first one, so some steps are its own
+        if self.steps.is_empty() {
+            return Err(SerializationError::InvalidData);
+        }
+        self.steps.check()
+    }
+}
+
+impl<E: IVC> CanonicalDeserialize for MergedInput<E> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        // same as in serialization, erase the reader type
+        let merged = Self::deserialize_at(&mut reader, compress, 1)?;
+        if validate == Validate::Yes {
+            merged.check()?;
+        }
+        Ok(merged)
+    }
+}
+
 impl<E: IVC> IVCStep<E> {
     pub fn new(
         proof: &<<E as IVC>::Snark as SNARK<E::Field>>::Proof,
         state: &StateHash<E::Field>,
         nullifier: &Nullifier<E::Field>,
-        sender: &Address<E::Field>,
+        time: u64,
     ) -> Self {
         IVCStep {
             proof: proof.clone(),
             state: *state,
             nullifier: *nullifier,
-            sender: *sender,
+            time,
+            merged: None,
+            issuance: None,
         }
     }
+
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    pub fn issuance(&self) -> Option<&Issuance<E::Field>> {
+        self.issuance.as_ref()
+    }
+
+    pub fn with_merge(mut self, merged: MergedInput<E>) -> Self {
+        self.merged = Some(merged);
+        self
+    }
+
+    pub fn with_issuance(mut self, issuance: &Issuance<E::Field>) -> Self {
+        self.issuance = Some(*issuance);
+        self
+    }
+
+    // decodes a step at the nesting level `depth`, its merged input is one level deeper
+    fn deserialize_at(
+        reader: &mut dyn Read,
+        compress: Compress,
+        depth: usize,
+    ) -> Result<Self, SerializationError> {
+        let validate = Validate::No;
+        deserialize_version(&mut *reader, compress)?;
+        let proof = CanonicalDeserialize::deserialize_with_mode(&mut *reader, compress, validate)?;
+        let state = StateHash::deserialize_with_mode(&mut *reader, compress, validate)?;
+        let nullifier = Nullifier::deserialize_with_mode(&mut *reader, compress, validate)?;
+        let time = u64::deserialize_with_mode(&mut *reader, compress, validate)?;
+        let merged = match bool::deserialize_with_mode(&mut *reader, compress, validate)? {
+            true => Some(MergedInput::deserialize_at(
+                &mut *reader,
+                compress,
+                depth + 1,
+            )?),
+            false => None,
+        };
+        let issuance = Option::deserialize_with_mode(&mut *reader, compress, validate)?;
+        Ok(IVCStep {
+            proof,
+            state,
+            nullifier,
+            time,
+            merged,
+            issuance,
+        })
+    }
+}
+
+impl<E: IVC> CanonicalSerialize for IVCStep<E> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_version(&mut writer, compress)?;
+        self.proof.serialize_with_mode(&mut writer, compress)?;
+        self.state.serialize_with_mode(&mut writer, compress)?;
+        self.nullifier.serialize_with_mode(&mut writer, compress)?;
+        self.time.serialize_with_mode(&mut writer, compress)?;
+        self.merged.serialize_with_mode(&mut writer, compress)?;
+        self.issuance.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        1 + self.proof.serialized_size(compress)
+            + self.state.serialized_size(compress)
+            + self.nullifier.serialized_size(compress)
+            + self.time.serialized_size(compress)
+            + self.merged.serialized_size(compress)
+            + self.issuance.serialized_size(compress)
+    }
+}
+
+impl<E: IVC> Valid for IVCStep<E> {
+    fn check(&self) -> Result<(), SerializationError> {
+        self.proof.check()?;
+        self.merged.check()?;
+        self.issuance.check()
+    }
+}
+
+impl<E: IVC> CanonicalDeserialize for IVCStep<E> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let step = Self::deserialize_at(&mut reader, compress, 0)?;
+        if validate == Validate::Yes {
+            step.check()?;
+        }
+        Ok(step)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -119,8 +588,8 @@ pub struct NoteHistory<E: IVC> {
 
     // unspent note
     pub(crate) current_note: Note<E::Field>,
-    // sibling of unspent note
-    pub(crate) sibling: BlindNoteHash<E::Field>,
+    // merkle path of unspent note in the last state
+    pub(crate) path: [BlindNoteHash<E::Field>; STATE_DEPTH],
 }
 
 impl<E: IVC> NoteHistory<E> {
@@ -128,40 +597,187 @@ impl<E: IVC> NoteHistory<E> {
         h: &PoseidonConfigs<E::Field>,
         asset: &Asset<E::Field>,
         issue_tx: &IssueTx<E::Field>,
+        issuance: &Issuance<E::Field>,
         proof: &<<E as IVC>::Snark as SNARK<E::Field>>::Proof,
+        time: u64,
     ) -> Self {
         let note = issue_tx.note;
+        let (_, blind_note_hash) = h.note(&note);
         let state = h.state_out_from_issue_tx(issue_tx);
-        let step = IVCStep::new(proof, &state, &Default::default(), &issue_tx.issuer);
+        let step = IVCStep::new(proof, &state, &Default::default(), time).with_issuance(issuance);
         NoteHistory {
             asset: *asset,
             steps: vec![step],
             current_note: note,
-            sibling: BlindNoteHash::default(),
+            path: h.state_path(&[blind_note_hash], 0),
         }
     }
 
+    pub fn asset(&self) -> &Asset<E::Field> {
+        &self.asset
+    }
+
     pub fn owner(&self) -> &Address<E::Field> {
         &self.current_note.owner
     }
 
+    pub fn value(&self) -> u64 {
+        self.current_note.value
+    }
+
+    // number of transactions from the issuance to the unspent note
+    pub fn depth(&self) -> usize {
+        self.steps.len()
+    }
+
     pub fn out_index(&self) -> &NoteOutIndex {
         &self.current_note.out_index
     }
 
-    pub fn sibling(&self) -> &BlindNoteHash<E::Field> {
-        &self.sibling
+    pub fn path(&self) -> &[BlindNoteHash<E::Field>; STATE_DEPTH] {
+        &self.path
     }
 
     pub fn state(&self, h: &PoseidonConfigs<E::Field>) -> StateHash<E::Field> {
         let (_, blind_note_hash) = h.note(&self.current_note);
-        match self.current_note.out_index {
-            NoteOutIndex::Out0 => h.state(&blind_note_hash, &self.sibling),
-            NoteOutIndex::Out1 => h.state(&self.sibling, &blind_note_hash),
-            NoteOutIndex::Issue => {
-                assert_eq!(self.sibling, BlindNoteHash::default());
-                h.state(&BlindNoteHash::default(), &blind_note_hash)
-            }
+        h.state_from_path(
+            &blind_note_hash,
+            self.current_note.out_index.position(),
+            &self.path,
+        )
+    }
+}
+
+impl<E: IVC> CanonicalSerialize for NoteHistory<E> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_version(&mut writer, compress)?;
+        self.asset.serialize_with_mode(&mut writer, compress)?;
+        self.steps.serialize_with_mode(&mut writer, compress)?;
+        self.current_note
+            .serialize_with_mode(&mut writer, compress)?;
+        self.path.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        1 + self.asset.serialized_size(compress)
+            + self.steps.serialized_size(compress)
+            + self.current_note.serialized_size(compress)
+            + self.path.serialized_size(compress)
+    }
+}
+
+impl<E: IVC> Valid for NoteHistory<E> {
+    fn check(&self) -> Result<(), SerializationError> {
+        // history starts with the issue step
+        if self.steps.is_empty() {
+            return Err(SerializationError::InvalidData);
+        }
+        // issued note is alone in its state
+        if self.current_note.out_index == NoteOutIndex::Issue
+            && self.path[0] != BlindNoteHash::default()
+        {
+            return Err(SerializationError::InvalidData);
+        }
+        self.asset.check()?;
+        self.steps.check()?;
+        self.current_note.check()?;
+        self.path.check()
+    }
+}
+
+impl<E: IVC> CanonicalDeserialize for NoteHistory<E> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        // parts are checked once with the whole history
+        deserialize_version(&mut reader, compress)?;
+        let history = NoteHistory {
+            asset: Asset::deserialize_with_mode(&mut reader, compress, Validate::No)?,
+            steps: deserialize_steps(&mut reader, compress, 0)?,
+            current_note: Note::deserialize_with_mode(&mut reader, compress, Validate::No)?,
+            path: <[BlindNoteHash<E::Field>; STATE_DEPTH]>::deserialize_with_mode(
+                &mut reader,
+                compress,
+                Validate::No,
+            )?,
+        };
+        if validate == Validate::Yes {
+            history.check()?;
         }
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IVCStep, NoteHistory};
+    use crate::asset::{Asset, Terms};
+    use crate::circuit::setup;
+    use crate::id::Auth;
+    use crate::poseidon::PoseidonConfigs;
+    use crate::test::Bls;
+    use crate::wallet::registry::MemoryRegistry;
+    use crate::wallet::{CommReceiver, Wallet};
+    use crate::ENCODING_VERSION;
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type F = ark_bls12_381::Fr;
+
+    #[test]
+    fn test_encoding_version() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let h = PoseidonConfigs::<F>::generate();
+        let shared = Rc::new(RefCell::new(MemoryRegistry::<F>::new()));
+        let mut wallet = || {
+            let auth = Auth::<Bls>::generate(&h, &mut rng).unwrap();
+            let (prover, verifier) = setup::<Bls, _>(&h, &mut rng).unwrap();
+            Wallet::new(auth, &h, prover, verifier, shared.clone())
+        };
+        let mut issuer = wallet();
+        let mut alice = wallet();
+        let asset = Asset::new(CommReceiver::address(&issuer), &Terms::iou(10, 1));
+        issuer.issue(&mut rng, 0, &mut alice, &asset, 10).unwrap();
+        let history = alice.accounts()[0].spendables()[0].clone();
+
+        let mut bytes = vec![];
+        history.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), history.compressed_size());
+        let decoded = NoteHistory::<Bls>::deserialize_compressed(&bytes[..]).unwrap();
+        assert_eq!(decoded.state(&h), history.state(&h));
+
+        // nested items are versioned too
+        let mut bytes = vec![];
+        history.steps[0].serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(bytes[0], ENCODING_VERSION);
+        IVCStep::<Bls>::deserialize_compressed(&bytes[..]).unwrap();
+        bytes[0] += 1;
+        assert!(IVCStep::<Bls>::deserialize_compressed(&bytes[..]).is_err());
+
+        let mut bytes = vec![];
+        history
+            .current_note
+            .serialize_compressed(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes[0], ENCODING_VERSION);
+        let mut bytes = vec![];
+        history.asset.serialize_compressed(&mut bytes).unwrap();
+        assert_eq!(bytes[0], ENCODING_VERSION);
+
+        // a history without steps is rejected when validated
+        let mut empty = history.clone();
+        empty.steps.clear();
+        let mut bytes = vec![];
+        empty.serialize_compressed(&mut bytes).unwrap();
+        assert!(NoteHistory::<Bls>::deserialize_compressed(&bytes[..]).is_err());
+        NoteHistory::<Bls>::deserialize_compressed_unchecked(&bytes[..]).unwrap();
     }
 }
@@ -0,0 +1,632 @@
+use crate::{
+    asset::Asset,
+    circuit::inputs::NoteVar,
+    note::Note,
+    tx::{IssueTx, MergeTx, RedeemTx, SplitTx, MAX_OUTPUTS},
+    Address, AssetHash, Blind, BlindNoteHash, FWrap, NoteHash, Nullifier, NullifierKey, SigHash,
+    StateHash, SupplyHash,
+};
+use ark_crypto_primitives::sponge::{
+    constraints::CryptographicSpongeVar,
+    poseidon::{constraints::PoseidonSpongeVar, PoseidonConfig, PoseidonSponge},
+    Absorb, CryptographicSponge,
+};
+use ark_ec::twisted_edwards::TECurveConfig;
+use ark_ff::{BigInteger, PrimeField};
+use ark_r1cs_std::R1CSVar;
+use ark_r1cs_std::{
+    boolean::Boolean, fields::fp::FpVar, groups::curves::twisted_edwards::AffineVar,
+};
+use ark_relations::r1cs::{ConstraintSystemRef, Namespace, Result as CSResult};
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
+};
+use arkeddsa::PublicKey;
+use digest::Digest;
+use params::DomainSpec;
+
+pub mod params;
+
+// domain tags, set as the capacity element before absorbing. eddsa hashes in
+// arkeddsa with the plain zero capacity, so zero is left to it
+const DOMAIN_ID: u64 = 1;
+const DOMAIN_NOTE: u64 = 2;
+const DOMAIN_BLIND: u64 = 3;
+const DOMAIN_STATE: u64 = 4;
+const DOMAIN_NULLIFIER: u64 = 5;
+const DOMAIN_TX: u64 = 6;
+const DOMAIN_MERGE: u64 = 7;
+const DOMAIN_RECEIPT: u64 = 8;
+const DOMAIN_ASSET: u64 = 9;
+const DOMAIN_SUPPLY: u64 = 10;
+const DOMAIN_KEY: u64 = 11;
+
+// depth of the state tree that commits to transaction outputs
+pub const STATE_DEPTH: usize = MAX_OUTPUTS.trailing_zeros() as usize;
+
+pub trait ToCRH<F: PrimeField> {
+    type Output;
+    fn to_crh(&self) -> Vec<Self::Output>;
+}
+
+impl<F: PrimeField + Absorb> ToCRH<F> for Note<F> {
+    type Output = F;
+
+    // serialize into field elements
+    fn to_crh(&self) -> Vec<F> {
+        let asset_hash = self.asset_hash.inner();
+        let owner = self.owner.inner();
+        let value = self.value.into();
+        let step = self.step.into();
+        let parent = self.parent_note.inner();
+        let out_index = self.out_index.inner();
+        vec![asset_hash, owner, value, step, parent, out_index]
+    }
+}
+
+pub(crate) fn field_cast<'a, F1: PrimeField, F2: PrimeField>(
+    x: &[F1],
+    dest: &'a mut Vec<F2>,
+) -> Option<&'a mut Vec<F2>> {
+    if F1::characteristic() != F2::characteristic() {
+        // "Trying to absorb non-native field elements."
+        None
+    } else {
+        x.iter().for_each(|item| {
+            let bytes = item.into_bigint().to_bytes_le();
+            dest.push(F2::from_le_bytes_mod_order(&bytes))
+        });
+        Some(dest)
+    }
+}
+
+impl<F: PrimeField + Absorb> Absorb for Note<F> {
+    fn to_sponge_bytes(&self, _: &mut Vec<u8>) {
+        unimplemented!();
+    }
+
+    fn to_sponge_field_elements<FF: PrimeField>(&self, dest: &mut Vec<FF>) {
+        field_cast(&self.to_crh(), dest).unwrap();
+    }
+}
+
+// sponge hash under the domain tag. domains of the same width share the standard
+// round constants and mds matrix, the tag in the capacity keeps them apart
+fn hash<F: PrimeField + Absorb>(config: &PoseidonConfig<F>, domain: u64, input: Vec<F>) -> F {
+    let mut sponge = PoseidonSponge::new(config);
+    sponge.state[0] = F::from(domain);
+    sponge.absorb(&input);
+    sponge.squeeze_field_elements::<F>(1)[0]
+}
+
+fn var_hash<F: PrimeField + Absorb>(
+    cs: ConstraintSystemRef<F>,
+    config: &PoseidonConfig<F>,
+    domain: u64,
+    input: &[FpVar<F>],
+) -> CSResult<FpVar<F>> {
+    let cs = cs.or(input.cs());
+    if cs.is_none() {
+        let input = input
+            .iter()
+            .map(|e| e.value())
+            .collect::<CSResult<Vec<_>>>()?;
+        return Ok(FpVar::Constant(hash(config, domain, input)));
+    }
+    let mut sponge = PoseidonSpongeVar::new(cs, config);
+    sponge.state[0] = FpVar::Constant(F::from(domain));
+    sponge.absorb(&input)?;
+    Ok(sponge.squeeze_field_elements(1)?.remove(0))
+}
+
+fn serialize_config<F: PrimeField, W: Write>(
+    config: &PoseidonConfig<F>,
+    mut writer: W,
+    compress: Compress,
+) -> Result<(), SerializationError> {
+    (config.full_rounds as u64).serialize_with_mode(&mut writer, compress)?;
+    (config.partial_rounds as u64).serialize_with_mode(&mut writer, compress)?;
+    config.alpha.serialize_with_mode(&mut writer, compress)?;
+    config.ark.serialize_with_mode(&mut writer, compress)?;
+    config.mds.serialize_with_mode(&mut writer, compress)?;
+    (config.rate as u64).serialize_with_mode(&mut writer, compress)?;
+    (config.capacity as u64).serialize_with_mode(&mut writer, compress)
+}
+
+fn deserialize_usize<R: Read>(
+    reader: R,
+    compress: Compress,
+    validate: Validate,
+) -> Result<usize, SerializationError> {
+    u64::deserialize_with_mode(reader, compress, validate)?
+        .try_into()
+        .map_err(|_| SerializationError::InvalidData)
+}
+
+fn deserialize_config<F: PrimeField, R: Read>(
+    mut reader: R,
+    compress: Compress,
+    validate: Validate,
+) -> Result<PoseidonConfig<F>, SerializationError> {
+    let full_rounds = deserialize_usize(&mut reader, compress, validate)?;
+    let partial_rounds = deserialize_usize(&mut reader, compress, validate)?;
+    let alpha = u64::deserialize_with_mode(&mut reader, compress, validate)?;
+    let ark = Vec::<Vec<F>>::deserialize_with_mode(&mut reader, compress, validate)?;
+    let mds = Vec::<Vec<F>>::deserialize_with_mode(&mut reader, compress, validate)?;
+    let rate = deserialize_usize(&mut reader, compress, validate)?;
+    let capacity = deserialize_usize(&mut reader, compress, validate)?;
+
+    // shape checks that `PoseidonConfig::new` would otherwise panic on
+    let width = rate
+        .checked_add(capacity)
+        .ok_or(SerializationError::InvalidData)?;
+    let rounds = full_rounds
+        .checked_add(partial_rounds)
+        .ok_or(SerializationError::InvalidData)?;
+    let valid = ark.len() == rounds
+        && ark.iter().all(|row| row.len() == width)
+        && mds.len() == width
+        && mds.iter().all(|row| row.len() == width);
+    valid
+        .then(|| PoseidonConfig::new(full_rounds, partial_rounds, alpha, mds, ark, rate, capacity))
+        .ok_or(SerializationError::InvalidData)
+}
+
+#[derive(Clone, Debug)]
+pub struct PoseidonConfigs<F: PrimeField + Absorb> {
+    pub(crate) id: PoseidonConfig<F>,
+    pub(crate) note: PoseidonConfig<F>,
+    pub(crate) blind: PoseidonConfig<F>,
+    pub(crate) state: PoseidonConfig<F>,
+    pub(crate) nullifier: PoseidonConfig<F>,
+    pub(crate) tx: PoseidonConfig<F>,
+    pub(crate) eddsa: PoseidonConfig<F>,
+    pub(crate) merge: PoseidonConfig<F>,
+    pub(crate) receipt: PoseidonConfig<F>,
+    pub(crate) asset: PoseidonConfig<F>,
+    pub(crate) supply: PoseidonConfig<F>,
+}
+
+impl<F: PrimeField + Absorb> PoseidonConfigs<F> {
+    // generate parameters for all domains. rate of each domain is the number of absorbed elements
+    pub fn generate() -> Self {
+        Self {
+            // nullifier key, public key x and y
+            id: DomainSpec::new::<F>(3).config(),
+            // see `ToCRH` for `Note`
+            note: DomainSpec::new::<F>(6).config(),
+            // note hash and blinding factor
+            blind: DomainSpec::new::<F>(2).config(),
+            // left and right nodes of the state tree
+            state: DomainSpec::new::<F>(2).config(),
+            // note hash and nullifier key
+            nullifier: DomainSpec::new::<F>(2).config(),
+            // input and output state
+            tx: DomainSpec::new::<F>(2).config(),
+            // signature r, public key and the message
+            eddsa: DomainSpec::new::<F>(5).config(),
+            // inputs and output state of merge transactions
+            merge: DomainSpec::new::<F>(3).config(),
+            // asset hash, nullifier and value of redemption receipts
+            receipt: DomainSpec::new::<F>(3).config(),
+            // issuer, terms digest, maturity, expiry and supply cap
+            asset: DomainSpec::new::<F>(5).config(),
+            // asset hash, issued value and blinding factor
+            supply: DomainSpec::new::<F>(3).config(),
+        }
+    }
+
+    fn domains(&self) -> Vec<&PoseidonConfig<F>> {
+        vec![
+            &self.id,
+            &self.note,
+            &self.blind,
+            &self.state,
+            &self.nullifier,
+            &self.tx,
+            &self.eddsa,
+            &self.merge,
+            &self.receipt,
+            &self.asset,
+            &self.supply,
+        ]
+    }
+
+    // digest over parameters of all domains
+    pub fn fingerprint(&self) -> [u8; 32] {
+        let mut hasher = sha2::Sha256::new();
+        for config in self.domains() {
+            let mut bytes = vec![];
+            serialize_config(config, &mut bytes, Compress::Yes).unwrap();
+            hasher.update(bytes);
+        }
+        hasher.finalize().into()
+    }
+
+    // commitment to the nullifier key. address opens with it and the public key
+    // alone, so that others can check signatures against an address
+    pub fn key_commitment(&self, nullifier_key: &NullifierKey<F>) -> F {
+        hash(&self.id, DOMAIN_KEY, vec![nullifier_key.inner()])
+    }
+
+    pub fn id_commitment<TE: TECurveConfig<BaseField = F>>(
+        &self,
+        nullifier_key: &NullifierKey<F>,
+        public_key: &PublicKey<TE>,
+    ) -> Address<F> {
+        self.id_opening(&self.key_commitment(nullifier_key), public_key)
+    }
+
+    pub fn id_opening<TE: TECurveConfig<BaseField = F>>(
+        &self,
+        key_commitment: &F,
+        public_key: &PublicKey<TE>,
+    ) -> Address<F> {
+        let (x, y) = public_key.xy();
+        let input = vec![*key_commitment, *x, *y];
+        hash(&self.id, DOMAIN_ID, input).into()
+    }
+
+    pub fn var_id_commitment<TE: TECurveConfig<BaseField = F>>(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        nullifier_key: &FpVar<F>,
+        public_key: &AffineVar<TE, FpVar<F>>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let key_commitment = var_hash(cs.clone(), &self.id, DOMAIN_KEY, &[nullifier_key.clone()])?;
+        let (x, y) = (public_key.x.clone(), public_key.y.clone());
+        let input = vec![key_commitment, x, y];
+        var_hash(cs, &self.id, DOMAIN_ID, &input)
+    }
+
+    // asset commitment. time bounds are in the clear so that the circuit can
+    // reason about them, rest of the terms are digested
+    pub fn asset(&self, asset: &Asset<F>) -> AssetHash<F> {
+        let terms = asset.terms();
+        let input = vec![
+            asset.issuer().inner(),
+            terms.digest(),
+            terms.maturity().into(),
+            terms.expiry().into(),
+            terms.cap().unwrap_or(u64::MAX).into(),
+        ];
+        hash(&self.asset, DOMAIN_ASSET, input).into()
+    }
+
+    pub fn var_asset(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        issuer: &FpVar<F>,
+        terms: &FpVar<F>,
+        maturity: &FpVar<F>,
+        expiry: &FpVar<F>,
+        cap: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let input = vec![
+            issuer.clone(),
+            terms.clone(),
+            maturity.clone(),
+            expiry.clone(),
+            cap.clone(),
+        ];
+        var_hash(cs, &self.asset, DOMAIN_ASSET, &input)
+    }
+
+    // commitment to the value an issuer has issued of an asset so far
+    pub fn supply(
+        &self,
+        asset_hash: &AssetHash<F>,
+        issued: u64,
+        blind: &Blind<F>,
+    ) -> SupplyHash<F> {
+        let input = vec![asset_hash.inner(), issued.into(), blind.inner()];
+        hash(&self.supply, DOMAIN_SUPPLY, input).into()
+    }
+
+    pub fn var_supply(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        asset_hash: &FpVar<F>,
+        issued: &FpVar<F>,
+        blind: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let input = vec![asset_hash.clone(), issued.clone(), blind.clone()];
+        var_hash(cs, &self.supply, DOMAIN_SUPPLY, &input)
+    }
+
+    pub fn note(&self, note: &Note<F>) -> (NoteHash<F>, BlindNoteHash<F>) {
+        let input = note.to_crh();
+        let note_hash = hash(&self.note, DOMAIN_NOTE, input).into();
+        let blind = self.blind_note(&note_hash, &note.blind);
+        (note_hash, blind)
+    }
+
+    pub fn blind_note(&self, note: &NoteHash<F>, blind: &Blind<F>) -> BlindNoteHash<F> {
+        let input = vec![note.inner(), blind.inner()];
+        hash(&self.blind, DOMAIN_BLIND, input).into()
+    }
+
+    pub fn var_note(&self, cs: impl Into<Namespace<F>>, note: &NoteVar<F>) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let input = note.to_crh();
+        var_hash(cs, &self.note, DOMAIN_NOTE, &input)
+    }
+
+    pub fn var_blind_note(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        note_hash: &FpVar<F>,
+        blind: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let input = vec![note_hash.clone(), blind.clone()];
+        var_hash(cs, &self.blind, DOMAIN_BLIND, &input)
+    }
+
+    pub fn state_out_from_issue_tx(&self, tx: &IssueTx<F>) -> StateHash<F> {
+        let (_, blind_note_hash) = self.note(tx.note());
+        self.state_root(&[blind_note_hash])
+    }
+
+    pub fn state_out_from_split_tx(&self, tx: &SplitTx<F>) -> StateHash<F> {
+        self.state_root(&self.leaves(tx.notes_out()))
+    }
+
+    pub fn state_out_from_merge_tx(&self, tx: &MergeTx<F>) -> StateHash<F> {
+        let (_, blind_note_hash) = self.note(tx.note_out());
+        self.state_root(&[blind_note_hash])
+    }
+
+    // blind note hashes of outputs in position order
+    pub fn leaves(&self, notes: &[Note<F>]) -> Vec<BlindNoteHash<F>> {
+        notes.iter().map(|note| self.note(note).1).collect()
+    }
+
+    // hash of two nodes of the state tree
+    pub fn state(&self, left: &BlindNoteHash<F>, right: &BlindNoteHash<F>) -> StateHash<F> {
+        let input = vec![left.inner(), right.inner()];
+        hash(&self.state, DOMAIN_STATE, input).into()
+    }
+
+    pub fn var_state(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        left: &FpVar<F>,
+        right: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let input = vec![left.clone(), right.clone()];
+        var_hash(cs, &self.state, DOMAIN_STATE, &input)
+    }
+
+    // layers of the state tree from leaves to the root. missing leaves are zero
+    fn state_tree(&self, leaves: &[BlindNoteHash<F>]) -> Vec<Vec<BlindNoteHash<F>>> {
+        assert!(leaves.len() <= MAX_OUTPUTS);
+        let mut layer = leaves.to_vec();
+        layer.resize(MAX_OUTPUTS, BlindNoteHash::default());
+        let mut tree = vec![layer];
+        while tree.last().unwrap().len() > 1 {
+            let layer = tree
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| self.state(&pair[0], &pair[1]).inner().into())
+                .collect();
+            tree.push(layer);
+        }
+        tree
+    }
+
+    // merkle root of output notes, commits to all outputs of a transaction
+    pub fn state_root(&self, leaves: &[BlindNoteHash<F>]) -> StateHash<F> {
+        self.state_tree(leaves).last().unwrap()[0].inner().into()
+    }
+
+    // siblings from the leaf at the position up to the root
+    pub fn state_path(
+        &self,
+        leaves: &[BlindNoteHash<F>],
+        position: usize,
+    ) -> [BlindNoteHash<F>; STATE_DEPTH] {
+        let tree = self.state_tree(leaves);
+        let mut path = [BlindNoteHash::default(); STATE_DEPTH];
+        path.iter_mut()
+            .zip(tree.iter())
+            .enumerate()
+            .for_each(|(level, (sibling, layer))| *sibling = layer[(position >> level) ^ 1]);
+        path
+    }
+
+    pub fn state_from_path(
+        &self,
+        leaf: &BlindNoteHash<F>,
+        position: usize,
+        path: &[BlindNoteHash<F>; STATE_DEPTH],
+    ) -> StateHash<F> {
+        let root = path
+            .iter()
+            .enumerate()
+            .fold(*leaf, |node, (level, sibling)| {
+                let state = match (position >> level) & 1 {
+                    0 => self.state(&node, sibling),
+                    _ => self.state(sibling, &node),
+                };
+                state.inner().into()
+            });
+        root.inner().into()
+    }
+
+    // `position` is little endian bits of the leaf position
+    pub fn var_state_from_path(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        leaf: &FpVar<F>,
+        position: &[Boolean<F>],
+        path: &[FpVar<F>],
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        position
+            .iter()
+            .zip(path.iter())
+            .try_fold(leaf.clone(), |node, (is_right, sibling)| {
+                let left = is_right.select(sibling, &node)?;
+                let right = is_right.select(&node, sibling)?;
+                self.var_state(cs.clone(), &left, &right)
+            })
+    }
+
+    pub fn var_state_root(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        leaves: &[FpVar<F>],
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        assert_eq!(leaves.len(), MAX_OUTPUTS);
+        let mut layer = leaves.to_vec();
+        while layer.len() > 1 {
+            layer = layer
+                .chunks(2)
+                .map(|pair| self.var_state(cs.clone(), &pair[0], &pair[1]))
+                .collect::<CSResult<Vec<_>>>()?;
+        }
+        Ok(layer.remove(0))
+    }
+
+    pub fn sighash_split_tx(&self, tx: &SplitTx<F>) -> SigHash<F> {
+        let (note_in, _) = self.note(&tx.note_in);
+        self.sighash(&note_in, &self.state_out_from_split_tx(tx))
+    }
+
+    pub fn sighash_issue_tx(&self, tx: &IssueTx<F>) -> SigHash<F> {
+        self.sighash(&Default::default(), &self.state_out_from_issue_tx(tx))
+    }
+
+    // signed message of issue and split transactions, input is zero for issue
+    pub fn sighash(&self, input: &NoteHash<F>, state_out: &StateHash<F>) -> SigHash<F> {
+        let input = vec![input.inner(), state_out.inner()];
+        hash(&self.tx, DOMAIN_TX, input).into()
+    }
+
+    pub fn var_sighash(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        input: &FpVar<F>,
+        state_out: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let input = vec![input.clone(), state_out.clone()];
+        var_hash(cs, &self.tx, DOMAIN_TX, &input)
+    }
+
+    // redeem has no outputs, it is signed against the zero state
+    pub fn sighash_redeem_tx(&self, tx: &RedeemTx<F>) -> SigHash<F> {
+        let (note_in, _) = self.note(&tx.note_in);
+        self.sighash(&note_in, &StateHash::default())
+    }
+
+    // message the issuer signs when it accepts a redemption
+    pub fn sighash_receipt(
+        &self,
+        asset_hash: &AssetHash<F>,
+        nullifier: &Nullifier<F>,
+        value: u64,
+    ) -> SigHash<F> {
+        let input = vec![asset_hash.inner(), nullifier.inner(), value.into()];
+        hash(&self.receipt, DOMAIN_RECEIPT, input).into()
+    }
+
+    pub fn sighash_merge_tx(&self, tx: &MergeTx<F>) -> SigHash<F> {
+        let (note_in_0, _) = self.note(&tx.note_in_0);
+        let (note_in_1, _) = self.note(&tx.note_in_1);
+        self.sighash_merge(&note_in_0, &note_in_1, &self.state_out_from_merge_tx(tx))
+    }
+
+    pub fn sighash_merge(
+        &self,
+        in0: &NoteHash<F>,
+        in1: &NoteHash<F>,
+        state_out: &StateHash<F>,
+    ) -> SigHash<F> {
+        let input = vec![in0.inner(), in1.inner(), state_out.inner()];
+        hash(&self.merge, DOMAIN_MERGE, input).into()
+    }
+
+    pub fn var_sighash_merge(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        in0: &FpVar<F>,
+        in1: &FpVar<F>,
+        state_out: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let input = vec![in0.clone(), in1.clone(), state_out.clone()];
+        var_hash(cs, &self.merge, DOMAIN_MERGE, &input)
+    }
+
+    pub fn nullifier(&self, note_in: &NoteHash<F>, key: &NullifierKey<F>) -> Nullifier<F> {
+        let input = vec![note_in.inner(), key.inner()];
+        hash(&self.nullifier, DOMAIN_NULLIFIER, input).into()
+    }
+
+    pub fn var_nullifier(
+        &self,
+        cs: impl Into<Namespace<F>>,
+        note: &FpVar<F>,
+        nullifier_key: &FpVar<F>,
+    ) -> CSResult<FpVar<F>> {
+        let cs = cs.into().cs();
+        let input = vec![note.clone(), nullifier_key.clone()];
+        var_hash(cs, &self.nullifier, DOMAIN_NULLIFIER, &input)
+    }
+}
+
+impl<F: PrimeField + Absorb> CanonicalSerialize for PoseidonConfigs<F> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        self.domains()
+            .into_iter()
+            .try_for_each(|config| serialize_config(config, &mut writer, compress))
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        let mut bytes = vec![];
+        self.serialize_with_mode(&mut bytes, compress).unwrap();
+        bytes.len()
+    }
+}
+
+impl<F: PrimeField + Absorb> Valid for PoseidonConfigs<F> {
+    fn check(&self) -> Result<(), SerializationError> {
+        Ok(())
+    }
+}
+
+impl<F: PrimeField + Absorb> CanonicalDeserialize for PoseidonConfigs<F> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let mut config = || deserialize_config(&mut reader, compress, validate);
+        Ok(Self {
+            id: config()?,
+            note: config()?,
+            blind: config()?,
+            state: config()?,
+            nullifier: config()?,
+            tx: config()?,
+            eddsa: config()?,
+            merge: config()?,
+            receipt: config()?,
+            asset: config()?,
+            supply: config()?,
+        })
+    }
+}
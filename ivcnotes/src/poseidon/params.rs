@@ -0,0 +1,164 @@
+use ark_crypto_primitives::sponge::poseidon::{find_poseidon_ark_and_mds, PoseidonConfig};
+use ark_ff::{BigInteger, PrimeField};
+
+// targeted security level in bits
+const SECURITY_LEVEL: f64 = 128.0;
+// candidate sbox exponents, smallest one that is a permutation is selected
+const ALPHA_CANDIDATES: [u64; 10] = [3, 5, 7, 11, 13, 17, 19, 23, 29, 31];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// parameters of a single hash domain
+pub struct DomainSpec {
+    // state width, rate plus single capacity element
+    pub width: usize,
+    // sbox exponent
+    pub alpha: u64,
+    // number of full rounds
+    pub full_rounds: usize,
+    // number of partial rounds
+    pub partial_rounds: usize,
+}
+
+impl DomainSpec {
+    // secure parameters for the domain absorbing `rate` field elements
+    pub fn new<F: PrimeField>(rate: usize) -> Self {
+        let width = rate + 1;
+        let alpha = alpha::<F>();
+        let (full_rounds, partial_rounds) = round_numbers(log2_modulus::<F>(), width, alpha);
+        Self {
+            width,
+            alpha,
+            full_rounds,
+            partial_rounds,
+        }
+    }
+
+    // standard round constants and mds matrix of the grain lfsr, the first mds
+    // candidate is taken. domains are separated by their tags, not by parameters
+    pub fn config<F: PrimeField>(&self) -> PoseidonConfig<F> {
+        let rate = self.width - 1;
+        let (ark, mds) = find_poseidon_ark_and_mds::<F>(
+            F::MODULUS_BIT_SIZE as u64,
+            rate,
+            self.full_rounds as u64,
+            self.partial_rounds as u64,
+            0,
+        );
+        PoseidonConfig::new(
+            self.full_rounds,
+            self.partial_rounds,
+            self.alpha,
+            mds,
+            ark,
+            rate,
+            1,
+        )
+    }
+}
+
+// `log2(p)` with sufficient precision for round number estimation
+fn log2_modulus<F: PrimeField>() -> f64 {
+    let bits = F::MODULUS_BIT_SIZE as usize;
+    let modulus = F::MODULUS.to_bits_be();
+    let modulus = &modulus[modulus.len() - bits..];
+    let top = modulus
+        .iter()
+        .take(64)
+        .fold(0u64, |acc, bit| (acc << 1) | (*bit as u64));
+    let shift = bits.saturating_sub(64) as f64;
+    shift + (top as f64).log2()
+}
+
+// smallest `alpha` that satisfies `gcd(alpha, p - 1) = 1`
+fn alpha<F: PrimeField>() -> u64 {
+    let p_minus_one = F::MODULUS_MINUS_ONE_DIV_TWO.to_bytes_be();
+    ALPHA_CANDIDATES
+        .into_iter()
+        .find(|alpha| {
+            // `p - 1 = 2 * (p - 1) / 2` and candidates are odd primes
+            let rem = p_minus_one
+                .iter()
+                .fold(0u64, |acc, byte| ((acc << 8) | *byte as u64) % alpha);
+            rem != 0
+        })
+        .expect("no suitable sbox exponent")
+}
+
+// `log2(n choose k)`
+fn log2_binomial(n: f64, k: f64) -> f64 {
+    if k < 0.0 || k > n {
+        return f64::NEG_INFINITY;
+    }
+    let k = k.min(n - k) as u64;
+    (1..=k).fold(0.0, |acc, i| {
+        let i = i as f64;
+        acc + ((n - k as f64 + i) / i).log2()
+    })
+}
+
+// checks round numbers against known attacks
+// see Poseidon paper section 5.5 and eprint 2023/537
+fn is_secure(log_p: f64, t: usize, full_rounds: usize, partial_rounds: usize, alpha: u64) -> bool {
+    let m = SECURITY_LEVEL;
+    let n = log_p.ceil();
+    let t = t as f64;
+    let (r_f, r_p) = (full_rounds as f64, partial_rounds as f64);
+    let alpha_f = alpha as f64;
+    let log_alpha = |x: f64| x.ln() / alpha_f.ln();
+
+    // statistical
+    let r_f_1 = if m <= (log_p - (alpha_f - 1.0) / 2.0).floor() * (t + 1.0) {
+        6.0
+    } else {
+        10.0
+    };
+    // interpolation
+    let r_f_2 = 1.0 + (log_alpha(2.0) * m.min(n)).ceil() + log_alpha(t).ceil() - r_p;
+    // groebner basis
+    let r_f_3 = log_alpha(2.0) * m.min(log_p) - r_p;
+    let r_f_4 = t - 1.0 + log_alpha(2.0) * (m / (t + 1.0)).min(log_p / 2.0) - r_p;
+    let r_f_5 = (t - 2.0 + m / (2.0 * alpha_f.log2()) - r_p) / (t - 1.0);
+    let r_f_max = [r_f_1, r_f_2, r_f_3, r_f_4, r_f_5]
+        .into_iter()
+        .map(f64::ceil)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    // eprint 2023/537
+    let r_temp = (t / 3.0).floor();
+    let over = (r_f - 1.0) * t + r_p + r_temp + r_temp * (r_f / 2.0) + r_p + alpha_f;
+    let under = r_temp * (r_f / 2.0) + r_p + alpha_f;
+    let binom_log = match log2_binomial(over, under) {
+        log if log.is_finite() => log,
+        _ => m + 1.0,
+    };
+
+    r_f >= r_f_max && (2.0 * binom_log).ceil() >= m
+}
+
+// minimum cost round numbers with the recommended security margin,
+// two additional full rounds and 7.5% more partial rounds
+fn round_numbers(log_p: f64, t: usize, alpha: u64) -> (usize, usize) {
+    let mut best: Option<(usize, usize, usize)> = None;
+    for partial_rounds in 1..500 {
+        for full_rounds in (4..100).step_by(2) {
+            if !is_secure(log_p, t, full_rounds, partial_rounds, alpha) {
+                continue;
+            }
+            let full_rounds = full_rounds + 2;
+            let partial_rounds = (partial_rounds as f64 * 1.075).ceil() as usize;
+            // sbox count
+            let cost = full_rounds * t + partial_rounds;
+            let better = match best {
+                None => true,
+                Some((best_cost, best_full_rounds, _)) => {
+                    cost < best_cost || (cost == best_cost && full_rounds < best_full_rounds)
+                }
+            };
+            if better {
+                best = Some((cost, full_rounds, partial_rounds));
+            }
+        }
+    }
+    let (_, full_rounds, partial_rounds) = best.expect("no secure round numbers");
+    (full_rounds, partial_rounds)
+}
@@ -0,0 +1,143 @@
+// mnemonic backed seeds and hierarchical derivation of identities
+use crate::{circuit::IVC, id::Auth, poseidon::PoseidonConfigs, FWrap, NullifierKey};
+use bip39::Mnemonic;
+use hmac::{Hmac, Mac};
+use rand_core::CryptoRngCore;
+use sha2::Sha512;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+type HmacSha512 = Hmac<Sha512>;
+
+// key of the master node hmac
+const MASTER_KEY: &[u8] = b"ivcnotes seed";
+// first element of account paths
+pub const ACCOUNT_PURPOSE: u32 = 0x4956_434e;
+// number of words in generated mnemonics
+const MNEMONIC_WORDS: usize = 24;
+
+fn hmac(key: &[u8], data: &[&[u8]]) -> [u8; 64] {
+    let mut mac = HmacSha512::new_from_slice(key).expect("hmac accepts any key size");
+    data.iter().for_each(|chunk| mac.update(chunk));
+    mac.finalize().into_bytes().into()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+// sequence of child indexes from the master node
+pub struct DerivationPath(Vec<u32>);
+
+impl DerivationPath {
+    pub fn new(indexes: &[u32]) -> Self {
+        Self(indexes.to_vec())
+    }
+
+    // path of the identity at the account index
+    pub fn account(index: u32) -> Self {
+        Self(vec![ACCOUNT_PURPOSE, index])
+    }
+
+    pub fn indexes(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+// node of the derivation tree
+struct Node {
+    key: [u8; 32],
+    chain_code: [u8; 32],
+}
+
+impl Node {
+    fn from_bytes(bytes: Zeroizing<[u8; 64]>) -> Self {
+        let mut node = Self {
+            key: [0u8; 32],
+            chain_code: [0u8; 32],
+        };
+        node.key.copy_from_slice(&bytes[..32]);
+        node.chain_code.copy_from_slice(&bytes[32..]);
+        node
+    }
+
+    fn child(&self, index: u32) -> Self {
+        let bytes = hmac(&self.chain_code, &[&[0], &self.key, &index.to_be_bytes()]);
+        Self::from_bytes(Zeroizing::new(bytes))
+    }
+}
+
+#[derive(Zeroize, ZeroizeOnDrop)]
+// 64 byte secret that all identities are derived from
+pub struct Seed([u8; 64]);
+
+impl Seed {
+    pub fn from_bytes(bytes: [u8; 64]) -> Self {
+        Self(bytes)
+    }
+
+    // new random mnemonic phrase to back up a seed
+    pub fn generate_mnemonic(rng: &mut impl CryptoRngCore) -> String {
+        let mut entropy = [0u8; MNEMONIC_WORDS / 3 * 4];
+        rng.fill_bytes(&mut entropy);
+        Mnemonic::from_entropy(&entropy)
+            .expect("entropy size is valid")
+            .to_string()
+    }
+
+    // bip39 seed of the phrase, passphrase can be empty. phrase and passphrase
+    // are nfkd normalized first
+    pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<Self, crate::Error> {
+        let mnemonic = Mnemonic::parse(phrase).map_err(|_err| crate::Error::BadMnemonic)?;
+        Ok(Self(mnemonic.to_seed(passphrase)))
+    }
+
+    fn node(&self, path: &DerivationPath) -> Node {
+        let master = Node::from_bytes(Zeroizing::new(hmac(MASTER_KEY, &[&self.0])));
+        path.indexes()
+            .iter()
+            .fold(master, |node, index| node.child(*index))
+    }
+
+    // nullifier key and signer seed of the identity at the path
+    pub(crate) fn secrets<E: IVC>(
+        &self,
+        path: &DerivationPath,
+    ) -> (NullifierKey<E::Field>, [u8; 32]) {
+        let node = self.node(path);
+        let nullifier_key =
+            NullifierKey::reduce_bytes(Zeroizing::new(hmac(&node.key, &[b"nullifier"])).as_ref());
+        let mut signer_seed = [0u8; 32];
+        signer_seed.copy_from_slice(&Zeroizing::new(hmac(&node.key, &[b"signer"]))[..32]);
+        (nullifier_key, signer_seed)
+    }
+}
+
+impl<E: IVC> Auth<E> {
+    // deterministic identity, same seed and path always give the same address
+    pub fn derive(h: &PoseidonConfigs<E::Field>, seed: &Seed, path: &DerivationPath) -> Self {
+        let (nullifier_key, signer_seed) = seed.secrets::<E>(path);
+        Self::from_secrets(h, nullifier_key, signer_seed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Seed;
+
+    const PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_mnemonic_seed() {
+        // bip39 test vector
+        let seed = Seed::from_mnemonic(PHRASE, "TREZOR").unwrap();
+        let expected = "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04";
+        let expected = (0..64)
+            .map(|i| u8::from_str_radix(&expected[2 * i..2 * i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(seed.0.to_vec(), expected);
+
+        // composed and decomposed forms of a passphrase give the same seed
+        let composed = Seed::from_mnemonic(PHRASE, "caf\u{e9}").unwrap();
+        let decomposed = Seed::from_mnemonic(PHRASE, "cafe\u{301}").unwrap();
+        assert_eq!(composed.0, decomposed.0);
+        assert!(Seed::from_mnemonic("abandon about", "").is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use crate::circuit::IVC;
+use ark_crypto_primitives::snark::SNARK;
+use ark_ff::PrimeField;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystem, SynthesisError};
+use rand::{CryptoRng, RngCore};
+use std::marker::PhantomData;
+
+// checks that the circuit is satisfied instead of proving, the proof is the
+// public input the circuit is synthesized with
+#[derive(Clone)]
+pub(crate) struct Mock<F>(PhantomData<F>);
+
+impl<F: PrimeField> SNARK<F> for Mock<F> {
+    type ProvingKey = ();
+    type VerifyingKey = ();
+    type ProcessedVerifyingKey = ();
+    type Proof = Vec<F>;
+    type Error = SynthesisError;
+
+    fn circuit_specific_setup<C: ConstraintSynthesizer<F>, R: RngCore + CryptoRng>(
+        _circuit: C,
+        _rng: &mut R,
+    ) -> Result<((), ()), SynthesisError> {
+        Ok(((), ()))
+    }
+
+    fn prove<C: ConstraintSynthesizer<F>, R: RngCore + CryptoRng>(
+        _pk: &(),
+        circuit: C,
+        _rng: &mut R,
+    ) -> Result<Vec<F>, SynthesisError> {
+        let cs = ConstraintSystem::<F>::new_ref();
+        circuit.generate_constraints(cs.clone())?;
+        cs.finalize();
+        assert!(cs.is_satisfied()?, "{:?}", cs.which_is_unsatisfied()?);
+        let public = cs.borrow().unwrap().instance_assignment[1..].to_vec();
+        Ok(public)
+    }
+
+    fn process_vk(_vk: &()) -> Result<(), SynthesisError> {
+        Ok(())
+    }
+
+    fn verify_with_processed_vk(
+        _vk: &(),
+        public: &[F],
+        proof: &Vec<F>,
+    ) -> Result<bool, SynthesisError> {
+        Ok(public == &proof[..])
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Bls;
+
+impl IVC for Bls {
+    type Snark = Mock<ark_bls12_381::Fr>;
+    type Field = ark_bls12_381::Fr;
+    type TE = ark_ed_on_bls12_381_bandersnatch::EdwardsConfig;
+    const CURVE_ID: &'static str = "bls12-381";
+}
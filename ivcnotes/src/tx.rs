@@ -5,15 +5,13 @@ use arkeddsa::signature::Signature;
 
 use crate::{
     note::{Note, NoteOutIndex},
-    Address, BlindNoteHash, Nullifier,
+    BlindNoteHash, Nullifier,
 };
 
 #[derive(Debug, Clone, Copy)]
 pub struct IssueTx<F: PrimeField> {
     // the first note in the propagation
     pub(crate) note: Note<F>,
-    // issuer address
-    pub(crate) issuer: Address<F>,
 }
 
 #[derive(Debug, Clone)]
@@ -28,15 +26,12 @@ where
 }
 
 impl<F: PrimeField + Absorb> IssueTx<F> {
-    pub(crate) fn new(issuer: &Address<F>, note: &Note<F>) -> Self {
+    pub(crate) fn new(note: &Note<F>) -> Self {
         assert_eq!(note.out_index, NoteOutIndex::Issue);
         assert_eq!(note.step, 0);
         assert_eq!(note.parent_note, BlindNoteHash::<F>::default());
 
-        IssueTx {
-            note: *note,
-            issuer: *issuer,
-        }
+        IssueTx { note: *note }
     }
 
     pub(crate) fn note(&self) -> &Note<F> {
@@ -68,11 +63,15 @@ where
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+// bound on outputs of a transaction, state tree has this many leaves so it must
+// be a power of two
+pub const MAX_OUTPUTS: usize = 8;
+
+#[derive(Debug, Clone)]
 pub struct SplitTx<F: PrimeField> {
     pub(crate) note_in: Note<F>,
-    pub(crate) note_out_0: Note<F>,
-    pub(crate) note_out_1: Note<F>,
+    // outputs in position order, first one is conventionally the change
+    pub(crate) notes_out: Vec<Note<F>>,
 }
 
 #[derive(Debug, Clone)]
@@ -89,11 +88,16 @@ where
 }
 
 impl<F: PrimeField + Absorb> SplitTx<F> {
-    pub(crate) fn new(note_in: &Note<F>, note_out_0: &Note<F>, note_out_1: &Note<F>) -> Self {
+    pub(crate) fn new(note_in: &Note<F>, notes_out: &[Note<F>]) -> Self {
+        assert!(!notes_out.is_empty() && notes_out.len() <= MAX_OUTPUTS);
+        notes_out
+            .iter()
+            .enumerate()
+            .for_each(|(i, note)| assert_eq!(note.out_index.position(), i));
+
         Self {
             note_in: *note_in,
-            note_out_0: *note_out_0,
-            note_out_1: *note_out_1,
+            notes_out: notes_out.to_vec(),
         }
     }
 
@@ -105,12 +109,8 @@ impl<F: PrimeField + Absorb> SplitTx<F> {
         SealedSplitTx::new(self, sig, nullifier)
     }
 
-    pub(crate) fn note_out_0(&self) -> &Note<F> {
-        &self.note_out_0
-    }
-
-    pub(crate) fn note_out_1(&self) -> &Note<F> {
-        &self.note_out_1
+    pub(crate) fn notes_out(&self) -> &[Note<F>] {
+        &self.notes_out
     }
 }
 
@@ -124,7 +124,7 @@ where
         nullifier: &Nullifier<TE::BaseField>,
     ) -> Self {
         SealedSplitTx {
-            tx: *tx,
+            tx: tx.clone(),
             signature: signature.clone(),
             nullifier: *nullifier,
         }
@@ -138,11 +138,149 @@ where
         &self.signature
     }
 
-    pub(crate) fn note_out_0(&self) -> &Note<TE::BaseField> {
-        self.tx.note_out_0()
+    pub(crate) fn notes_out(&self) -> &[Note<TE::BaseField>] {
+        self.tx.notes_out()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+// joins two notes of the same asset into a single note
+pub struct MergeTx<F: PrimeField> {
+    pub(crate) note_in_0: Note<F>,
+    pub(crate) note_in_1: Note<F>,
+    pub(crate) note_out: Note<F>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SealedMergeTx<TE: TECurveConfig + Clone>
+where
+    TE::BaseField: PrimeField + Absorb,
+{
+    // wrap the transaction
+    pub(crate) tx: MergeTx<TE::BaseField>,
+    // store the signature
+    pub(crate) signature: Signature<TE>,
+    // and nullifiers of both inputs
+    pub(crate) nullifier_0: Nullifier<TE::BaseField>,
+    pub(crate) nullifier_1: Nullifier<TE::BaseField>,
+}
+
+impl<F: PrimeField + Absorb> MergeTx<F> {
+    pub(crate) fn new(note_in_0: &Note<F>, note_in_1: &Note<F>, note_out: &Note<F>) -> Self {
+        assert_eq!(note_out.out_index, NoteOutIndex::FIRST_OUT);
+        assert_eq!(note_in_0.asset_hash, note_in_1.asset_hash);
+        assert_eq!(note_in_0.asset_hash, note_out.asset_hash);
+        assert_eq!(
+            Some(note_out.value),
+            note_in_0.value.checked_add(note_in_1.value)
+        );
+
+        Self {
+            note_in_0: *note_in_0,
+            note_in_1: *note_in_1,
+            note_out: *note_out,
+        }
+    }
+
+    pub(crate) fn seal<TE: TECurveConfig<BaseField = F> + Clone>(
+        &self,
+        sig: &Signature<TE>,
+        nullifier_0: &Nullifier<TE::BaseField>,
+        nullifier_1: &Nullifier<TE::BaseField>,
+    ) -> SealedMergeTx<TE> {
+        SealedMergeTx::new(self, sig, nullifier_0, nullifier_1)
+    }
+
+    pub(crate) fn note_out(&self) -> &Note<F> {
+        &self.note_out
+    }
+}
+
+impl<TE: TECurveConfig + Clone> SealedMergeTx<TE>
+where
+    TE::BaseField: PrimeField + Absorb,
+{
+    pub(crate) fn new(
+        tx: &MergeTx<TE::BaseField>,
+        signature: &Signature<TE>,
+        nullifier_0: &Nullifier<TE::BaseField>,
+        nullifier_1: &Nullifier<TE::BaseField>,
+    ) -> Self {
+        SealedMergeTx {
+            tx: *tx,
+            signature: signature.clone(),
+            nullifier_0: *nullifier_0,
+            nullifier_1: *nullifier_1,
+        }
+    }
+
+    pub(crate) fn nullifier_0(&self) -> &Nullifier<TE::BaseField> {
+        &self.nullifier_0
+    }
+
+    pub(crate) fn nullifier_1(&self) -> &Nullifier<TE::BaseField> {
+        &self.nullifier_1
+    }
+
+    pub(crate) fn signature(&self) -> &Signature<TE> {
+        &self.signature
+    }
+
+    pub(crate) fn note_out(&self) -> &Note<TE::BaseField> {
+        self.tx.note_out()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+// retires a note, value leaves circulation and goes back to the issuer
+pub struct RedeemTx<F: PrimeField> {
+    pub(crate) note_in: Note<F>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SealedRedeemTx<TE: TECurveConfig + Clone>
+where
+    TE::BaseField: PrimeField + Absorb,
+{
+    // wrap the transaction
+    pub(crate) tx: RedeemTx<TE::BaseField>,
+    // store the signature
+    pub(crate) signature: Signature<TE>,
+    // and the nullifier
+    pub(crate) nullifier: Nullifier<TE::BaseField>,
+}
+
+impl<F: PrimeField + Absorb> RedeemTx<F> {
+    pub(crate) fn new(note_in: &Note<F>) -> Self {
+        Self { note_in: *note_in }
+    }
+
+    pub(crate) fn seal<TE: TECurveConfig<BaseField = F> + Clone>(
+        &self,
+        sig: &Signature<TE>,
+        nullifier: &Nullifier<TE::BaseField>,
+    ) -> SealedRedeemTx<TE> {
+        SealedRedeemTx {
+            tx: *self,
+            signature: sig.clone(),
+            nullifier: *nullifier,
+        }
+    }
+}
+
+impl<TE: TECurveConfig + Clone> SealedRedeemTx<TE>
+where
+    TE::BaseField: PrimeField + Absorb,
+{
+    pub(crate) fn nullifier(&self) -> &Nullifier<TE::BaseField> {
+        &self.nullifier
+    }
+
+    pub(crate) fn signature(&self) -> &Signature<TE> {
+        &self.signature
     }
 
-    pub(crate) fn note_out_1(&self) -> &Note<TE::BaseField> {
-        self.tx.note_out_1()
+    pub(crate) fn note_in(&self) -> &Note<TE::BaseField> {
+        &self.tx.note_in
     }
 }
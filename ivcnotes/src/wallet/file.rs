@@ -0,0 +1,406 @@
+use super::registry::NullifierRegistry;
+use super::{Account, Wallet};
+use crate::{
+    circuit::{Prover, Verifier, IVC},
+    id::Auth,
+    note::NoteHistory,
+    poseidon::PoseidonConfigs,
+    Address, NullifierKey,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::{CryptoRng, RngCore};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+const MAGIC: [u8; 4] = *b"IVCW";
+pub const WALLET_FORMAT_VERSION: u8 = 1;
+
+// argon2id cost parameters, memory is in KiB
+const KDF_MEMORY: u32 = 19 * 1024;
+const KDF_ITERATIONS: u32 = 2;
+const KDF_PARALLELISM: u32 = 1;
+// upper bounds accepted when reading, a crafted file must not exhaust memory or time
+const KDF_MAX_MEMORY: u32 = 1024 * 1024;
+const KDF_MAX_ITERATIONS: u32 = 16;
+const KDF_MAX_PARALLELISM: u32 = 16;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalletFileError {
+    // file doesn't start with the magic bytes
+    NotAWalletFile,
+    // wallet file is written with another format version
+    UnsupportedVersion(u8),
+    // key derivation parameters are out of range
+    BadKdfParams,
+    // secrets cannot be encrypted
+    Encryption,
+    // wrong passphrase or the file is tampered with
+    Decryption,
+    // restored secrets don't open to the stored addresses
+    AddressMismatch,
+}
+
+impl core::fmt::Display for WalletFileError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::NotAWalletFile => write!(f, "not a wallet file"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            Self::BadKdfParams => write!(f, "bad key derivation parameters"),
+            Self::Encryption => write!(f, "encryption failed"),
+            Self::Decryption => write!(f, "wrong passphrase or corrupted file"),
+            Self::AddressMismatch => write!(f, "secrets don't match the address"),
+        }
+    }
+}
+
+impl ark_std::error::Error for WalletFileError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KdfParams {
+    memory: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: [u8; SALT_LEN],
+}
+
+impl KdfParams {
+    fn new(rng: &mut impl RngCore) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        rng.fill_bytes(&mut salt);
+        Self {
+            memory: KDF_MEMORY,
+            iterations: KDF_ITERATIONS,
+            parallelism: KDF_PARALLELISM,
+            salt,
+        }
+    }
+
+    fn write(&self, mut writer: impl Write) -> Result<(), crate::Error> {
+        self.memory.serialize_compressed(&mut writer)?;
+        self.iterations.serialize_compressed(&mut writer)?;
+        self.parallelism.serialize_compressed(&mut writer)?;
+        Ok(self.salt.serialize_compressed(&mut writer)?)
+    }
+
+    fn read(mut reader: impl Read) -> Result<Self, crate::Error> {
+        let kdf = Self {
+            memory: u32::deserialize_compressed(&mut reader)?,
+            iterations: u32::deserialize_compressed(&mut reader)?,
+            parallelism: u32::deserialize_compressed(&mut reader)?,
+            salt: <[u8; SALT_LEN]>::deserialize_compressed(&mut reader)?,
+        };
+        (kdf.memory <= KDF_MAX_MEMORY
+            && kdf.iterations <= KDF_MAX_ITERATIONS
+            && kdf.parallelism <= KDF_MAX_PARALLELISM)
+            .then_some(kdf)
+            .ok_or(WalletFileError::BadKdfParams.into())
+    }
+
+    fn derive_key(&self, passphrase: &[u8]) -> Result<Zeroizing<[u8; 32]>, WalletFileError> {
+        let params = Params::new(self.memory, self.iterations, self.parallelism, Some(32))
+            .map_err(|_err| WalletFileError::BadKdfParams)?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase, &self.salt, key.as_mut())
+            .map_err(|_err| WalletFileError::BadKdfParams)?;
+        Ok(key)
+    }
+}
+
+impl<E: IVC> Wallet<E> {
+    // public part is stored in the clear and authenticated as associated data,
+    // nullifier key and signer seed are encrypted under the passphrase
+    pub fn write<W: Write, R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        mut writer: W,
+        passphrase: &[u8],
+    ) -> Result<(), crate::Error> {
+        let kdf = KdfParams::new(rng);
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+
+        let mut public = vec![];
+        public.extend_from_slice(&MAGIC);
+        WALLET_FORMAT_VERSION.serialize_compressed(&mut public)?;
+        self.h.serialize_compressed(&mut public)?;
+        (self.accounts.len() as u64).serialize_compressed(&mut public)?;
+        for account in self.accounts.iter() {
+            account.address().serialize_compressed(&mut public)?;
+            account.spendables.serialize_compressed(&mut public)?;
+            account.supply.serialize_compressed(&mut public)?;
+        }
+        kdf.write(&mut public)?;
+        nonce.serialize_compressed(&mut public)?;
+
+        let mut secrets = Zeroizing::new(vec![]);
+        for account in self.accounts.iter() {
+            account
+                .auth
+                .nullifier_key()
+                .serialize_compressed(&mut *secrets)?;
+            account
+                .auth
+                .signer_seed()
+                .serialize_compressed(&mut *secrets)?;
+        }
+
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(kdf.derive_key(passphrase)?.as_ref()));
+        let payload = Payload {
+            msg: &secrets,
+            aad: &public,
+        };
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_err| WalletFileError::Encryption)?;
+
+        writer.write_all(&public)?;
+        ciphertext.serialize_compressed(&mut writer)?;
+        Ok(())
+    }
+
+    // spends and supply links are not stored with the wallet, `registry` must be
+    // the one the wallet was used with
+    pub fn read<R: Read>(
+        mut reader: R,
+        passphrase: &[u8],
+        prover: Prover<E>,
+        verifier: Verifier<E>,
+        registry: impl NullifierRegistry<E::Field> + 'static,
+    ) -> Result<Self, crate::Error> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+        let mut cursor = &bytes[..];
+
+        let mut magic = [0u8; 4];
+        cursor.read_exact(&mut magic)?;
+        (magic == MAGIC)
+            .then_some(())
+            .ok_or(WalletFileError::NotAWalletFile)?;
+        let version = u8::deserialize_compressed(&mut cursor)?;
+        (version == WALLET_FORMAT_VERSION)
+            .then_some(())
+            .ok_or(WalletFileError::UnsupportedVersion(version))?;
+
+        let h = PoseidonConfigs::deserialize_compressed(&mut cursor)?;
+        let n_accounts = u64::deserialize_compressed(&mut cursor)?;
+        let mut public_accounts = vec![];
+        for _ in 0..n_accounts {
+            let address = Address::deserialize_compressed(&mut cursor)?;
+            let spendables = Vec::<NoteHistory<E>>::deserialize_compressed(&mut cursor)?;
+            let supply = BTreeMap::deserialize_compressed(&mut cursor)?;
+            public_accounts.push((address, spendables, supply));
+        }
+        (!public_accounts.is_empty())
+            .then_some(())
+            .ok_or(crate::Error::Serialization(
+                ark_serialize::SerializationError::InvalidData,
+            ))?;
+        let kdf = KdfParams::read(&mut cursor)?;
+        let nonce = <[u8; NONCE_LEN]>::deserialize_compressed(&mut cursor)?;
+        let public = &bytes[..bytes.len() - cursor.len()];
+        let ciphertext = Vec::<u8>::deserialize_compressed(&mut cursor)?;
+
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(kdf.derive_key(passphrase)?.as_ref()));
+        let payload = Payload {
+            msg: &ciphertext,
+            aad: public,
+        };
+        let secrets = cipher
+            .decrypt(Nonce::from_slice(&nonce), payload)
+            .map(Zeroizing::new)
+            .map_err(|_err| WalletFileError::Decryption)?;
+
+        let mut secrets = &secrets[..];
+        let accounts = public_accounts
+            .into_iter()
+            .map(|(address, spendables, supply)| {
+                let nullifier_key = NullifierKey::deserialize_compressed(&mut secrets)?;
+                let signer_seed = Zeroizing::new(<[u8; 32]>::deserialize_compressed(&mut secrets)?);
+                let auth = Auth::from_secrets(&h, nullifier_key, *signer_seed);
+                (*auth.address() == address)
+                    .then_some(Account {
+                        auth,
+                        spendables,
+                        supply,
+                    })
+                    .ok_or(WalletFileError::AddressMismatch.into())
+            })
+            .collect::<Result<Vec<_>, crate::Error>>()?;
+
+        Ok(Self {
+            accounts,
+            h,
+            prover,
+            verifier,
+            clock: super::unix_time,
+            freshness: super::FRESHNESS_WINDOW,
+            registry: Box::new(registry),
+        })
+    }
+
+    // write to a temporary file first so that a crash never leaves a partial wallet file behind
+    pub fn save<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        path: impl AsRef<Path>,
+        passphrase: &[u8],
+    ) -> Result<(), crate::Error> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let mut file = File::create(&tmp)?;
+        self.write(rng, &mut file, passphrase)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load(
+        path: impl AsRef<Path>,
+        passphrase: &[u8],
+        prover: Prover<E>,
+        verifier: Verifier<E>,
+        registry: impl NullifierRegistry<E::Field> + 'static,
+    ) -> Result<Self, crate::Error> {
+        Self::read(File::open(path)?, passphrase, prover, verifier, registry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{WalletFileError, KDF_ITERATIONS, KDF_MAX_ITERATIONS, KDF_MAX_MEMORY, KDF_MEMORY};
+    use crate::asset::{Asset, Terms};
+    use crate::circuit::setup;
+    use crate::id::Auth;
+    use crate::poseidon::PoseidonConfigs;
+    use crate::test::Bls;
+    use crate::wallet::registry::{self, MemoryRegistry};
+    use crate::wallet::{CommReceiver, Wallet};
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type F = ark_bls12_381::Fr;
+
+    #[test]
+    fn test_read_keeps_registry() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let h = PoseidonConfigs::<F>::generate();
+        let shared = Rc::new(RefCell::new(MemoryRegistry::<F>::new()));
+        let mut wallet = || {
+            let auth = Auth::<Bls>::generate(&h, &mut rng).unwrap();
+            let (prover, verifier) = setup::<Bls, _>(&h, &mut rng).unwrap();
+            Wallet::new(auth, &h, prover, verifier, shared.clone())
+        };
+        let mut issuer = wallet();
+        let mut alice = wallet();
+        let mut bob = wallet();
+
+        let asset = Asset::new(CommReceiver::address(&issuer), &Terms::iou(10, 1));
+        issuer.issue(&mut rng, 0, &mut alice, &asset, 10).unwrap();
+        alice.split(&mut rng, 0, &mut bob, 0, 4).unwrap();
+
+        let mut bytes = vec![];
+        alice.write(&mut rng, &mut bytes, b"passphrase").unwrap();
+        let (prover, verifier) = setup::<Bls, _>(&h, &mut rng).unwrap();
+        let mut alice =
+            Wallet::<Bls>::read(&bytes[..], b"passphrase", prover, verifier, shared.clone())
+                .unwrap();
+
+        // spends of the notes that left the wallet are still known
+        for (nullifier, spend) in registry::spends(&bob.accounts[0].spendables[0].steps) {
+            assert_eq!(alice.registry().get(&nullifier).unwrap(), Some(spend));
+        }
+        let spent = shared.borrow().len();
+        alice.split(&mut rng, 0, &mut bob, 0, 1).unwrap();
+        assert_eq!(shared.borrow().len(), spent + 1);
+    }
+
+    #[test]
+    fn test_read_rejects() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let h = PoseidonConfigs::<F>::generate();
+        let auth = Auth::<Bls>::generate(&h, &mut rng).unwrap();
+        let (prover, verifier) = setup::<Bls, _>(&h, &mut rng).unwrap();
+        let wallet = Wallet::new(auth, &h, prover, verifier, MemoryRegistry::<F>::new());
+        let mut bytes = vec![];
+        wallet.write(&mut rng, &mut bytes, b"passphrase").unwrap();
+
+        let mut read = |bytes: &[u8], passphrase: &[u8]| {
+            let (prover, verifier) = setup::<Bls, _>(&h, &mut rng).unwrap();
+            match Wallet::<Bls>::read(bytes, passphrase, prover, verifier, MemoryRegistry::new()) {
+                Ok(wallet) => Ok(*wallet.accounts[0].address()),
+                Err(crate::Error::WalletFile(err)) => Err(err),
+                Err(err) => panic!("{}", err),
+            }
+        };
+        assert_eq!(
+            read(&bytes, b"passphrase"),
+            Ok(*wallet.accounts[0].address())
+        );
+        assert_eq!(
+            read(&bytes, b"passphrase!"),
+            Err(WalletFileError::Decryption)
+        );
+
+        // kdf params are stored in the clear as memory, iterations, parallelism
+        let params = [KDF_MEMORY, KDF_ITERATIONS]
+            .iter()
+            .flat_map(|param| param.to_le_bytes())
+            .collect::<Vec<_>>();
+        let memory = bytes
+            .windows(params.len())
+            .position(|window| window == params)
+            .unwrap();
+        let iterations = memory + 4;
+        let set = |offset: usize, value: u32| {
+            let mut tampered = bytes.clone();
+            tampered[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            tampered
+        };
+
+        // header is authenticated, an in range change or another salt fails to decrypt
+        let tampered = set(iterations, KDF_ITERATIONS + 1);
+        assert_eq!(
+            read(&tampered, b"passphrase"),
+            Err(WalletFileError::Decryption)
+        );
+        let mut tampered = bytes.clone();
+        tampered[memory + 12] ^= 1;
+        assert_eq!(
+            read(&tampered, b"passphrase"),
+            Err(WalletFileError::Decryption)
+        );
+
+        // out of bounds params are rejected before running the kdf
+        let tampered = set(iterations, KDF_MAX_ITERATIONS + 1);
+        assert_eq!(
+            read(&tampered, b"passphrase"),
+            Err(WalletFileError::BadKdfParams)
+        );
+        let tampered = set(memory, KDF_MAX_MEMORY + 1);
+        assert_eq!(
+            read(&tampered, b"passphrase"),
+            Err(WalletFileError::BadKdfParams)
+        );
+
+        let mut tampered = bytes;
+        tampered[0] ^= 1;
+        assert_eq!(
+            read(&tampered, b"passphrase"),
+            Err(WalletFileError::NotAWalletFile)
+        );
+    }
+}
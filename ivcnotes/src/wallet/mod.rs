@@ -0,0 +1,782 @@
+use crate::{
+    asset::Asset,
+    circuit::{
+        inputs::{AuxInputs, NoteOpening, NoteOutput, PublicInput, SupplyOpening},
+        Prover, Verifier, IVC,
+    },
+    id::Auth,
+    note::{
+        merge_depth, IVCStep, Issuance, MergedInput, Note, NoteHistory, NoteOutIndex, StepPath,
+        MAX_MERGE_DEPTH,
+    },
+    poseidon::PoseidonConfigs,
+    tx::{IssueTx, MergeTx, SealedIssueTx, SealedMergeTx, SealedSplitTx, SplitTx, MAX_OUTPUTS},
+    Address, AssetHash, Blind, FWrap, StateHash,
+};
+
+use ark_crypto_primitives::snark::SNARK;
+use rand::{CryptoRng, RngCore};
+use redeem::Supply;
+use registry::NullifierRegistry;
+use std::collections::BTreeMap;
+
+pub mod file;
+pub mod pay;
+pub mod portfolio;
+pub mod redeem;
+pub mod registry;
+
+pub trait CommReceiver<E: IVC> {
+    fn receive(&mut self, history: &NoteHistory<E>) -> Result<(), crate::Error>;
+    fn address(&self) -> &Address<E::Field>;
+}
+
+// single identity of a wallet and notes it owns
+pub struct Account<E: IVC> {
+    // auth object that holds private keys
+    auth: Auth<E>,
+    // receivables are transferable notes
+    spendables: Vec<NoteHistory<E>>,
+    // ledgers of assets issued by this account
+    supply: BTreeMap<AssetHash<E::Field>, Supply<E::Field>>,
+}
+
+impl<E: IVC> Account<E> {
+    fn new(auth: Auth<E>) -> Self {
+        Self {
+            auth,
+            spendables: vec![],
+            supply: BTreeMap::new(),
+        }
+    }
+
+    pub fn address(&self) -> &Address<E::Field> {
+        self.auth.address()
+    }
+
+    pub fn spendables(&self) -> &[NoteHistory<E>] {
+        &self.spendables
+    }
+}
+
+pub struct Wallet<E: IVC> {
+    // identities, first one is the default account
+    accounts: Vec<Account<E>>,
+    // configs for poseidion hasher
+    h: PoseidonConfigs<E::Field>,
+    // prover
+    prover: Prover<E>,
+    // verifier
+    verifier: Verifier<E>,
+    // source of the current unix time
+    clock: fn() -> u64,
+    // seconds an incoming step of an expiring asset may lag behind the clock
+    freshness: u64,
+    // nullifiers spent by received histories and by this wallet
+    registry: Box<dyn NullifierRegistry<E::Field>>,
+}
+
+// default bound on the age of incoming steps of expiring assets. provers pick
+// the step time, so without a bound a transfer could be backdated past expiry
+pub const FRESHNESS_WINDOW: u64 = 60 * 60;
+
+// seconds since the unix epoch
+pub fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+impl<E: IVC> CommReceiver<E> for Wallet<E> {
+    // incoming notes are routed to the account that owns them
+    fn receive(&mut self, note_history: &NoteHistory<E>) -> Result<(), crate::Error> {
+        let account = self
+            .account_index(&note_history.current_note.owner)
+            .ok_or(crate::Error::NotRecipient)?;
+        // every note starts with an issue step
+        (!note_history.steps.is_empty())
+            .then_some(())
+            .ok_or(crate::Error::BadCurrentState)?;
+        // a note is identified by the step that created it and its output index. a
+        // held note delivered again is accepted and not added twice
+        let created = |history: &NoteHistory<E>| {
+            history
+                .steps
+                .last()
+                .map(|step| (step.state, history.current_note.out_index))
+        };
+        if self.accounts[account]
+            .spendables
+            .iter()
+            .any(|held| created(held) == created(note_history))
+        {
+            return Ok(());
+        }
+        (note_history.current_note.asset_hash == note_history.asset.hash(&self.h))
+            .then_some(())
+            .ok_or(crate::Error::AssetMismatch)?;
+        // transferred vouchers are only accepted by their merchant. senders check
+        // this too but nothing forces them to
+        (note_history.current_note.out_index == NoteOutIndex::Issue
+            || note_history
+                .asset
+                .terms()
+                .accepts(&note_history.current_note.owner))
+        .then_some(())
+        .ok_or(crate::Error::MerchantRestricted)?;
+
+        let (state, time) = self.verify_steps(&note_history.asset, &note_history.steps)?;
+        let now = (self.clock)();
+        let expiring = note_history.asset.terms().expiry() != u64::MAX;
+        (time <= now && (!expiring || now - time <= self.freshness))
+            .then_some(())
+            .ok_or_else(|| crate::Error::BadTimestamp {
+                step: StepPath::at(note_history.steps.len() - 1),
+            })?;
+        (note_history.state(&self.h) == state)
+            .then_some(())
+            .ok_or(crate::Error::BadCurrentState)?;
+
+        self.register_steps(&note_history.current_note.asset_hash, &note_history.steps)?;
+        self.accounts[account].spendables.push(note_history.clone());
+
+        Ok(())
+    }
+
+    // address of the default account
+    fn address(&self) -> &Address<E::Field> {
+        self.accounts[0].address()
+    }
+}
+
+impl<E: IVC> Auth<E> {
+    // sign issue transaction
+    pub(crate) fn issue(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        tx: &IssueTx<E::Field>,
+    ) -> Result<SealedIssueTx<E::TE>, crate::Error> {
+        let sighash = h.sighash_issue_tx(tx);
+        let signature = self.sign(&sighash);
+        Ok(tx.seal(signature))
+    }
+
+    // sign split transaction and generate the nullifier
+    pub(crate) fn split(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        tx: &SplitTx<E::Field>,
+    ) -> Result<SealedSplitTx<E::TE>, crate::Error> {
+        let sighash = h.sighash_split_tx(tx);
+        let signature = self.sign(&sighash);
+        let (note_in, _) = h.note(&tx.note_in);
+        let nullifier = h.nullifier(&note_in, self.nullifier_key());
+        Ok(tx.seal(&signature, &nullifier))
+    }
+
+    // sign merge transaction and generate nullifiers of both inputs
+    pub(crate) fn merge(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        tx: &MergeTx<E::Field>,
+    ) -> Result<SealedMergeTx<E::TE>, crate::Error> {
+        let sighash = h.sighash_merge_tx(tx);
+        let signature = self.sign(&sighash);
+        let (note_in_0, _) = h.note(&tx.note_in_0);
+        let (note_in_1, _) = h.note(&tx.note_in_1);
+        let nullifier_0 = h.nullifier(&note_in_0, self.nullifier_key());
+        let nullifier_1 = h.nullifier(&note_in_1, self.nullifier_key());
+        Ok(tx.seal(&signature, &nullifier_0, &nullifier_1))
+    }
+}
+
+impl<E: IVC> Wallet<E> {
+    // only issuances published to the registry are received, so wallets exchanging
+    // notes share one, e.g. a relay or a `FileRegistry` on the same machine
+    pub fn new(
+        auth: Auth<E>,
+        poseidon: &PoseidonConfigs<E::Field>,
+        prover: Prover<E>,
+        verifier: Verifier<E>,
+        registry: impl NullifierRegistry<E::Field> + 'static,
+    ) -> Self {
+        Self {
+            accounts: vec![Account::new(auth)],
+            h: poseidon.clone(),
+            prover,
+            verifier,
+            clock: unix_time,
+            freshness: FRESHNESS_WINDOW,
+            registry: Box::new(registry),
+        }
+    }
+
+    // replace the system clock, time is used in proofs and to check incoming steps
+    pub fn with_clock(mut self, clock: fn() -> u64) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    // replace the bound on the age of incoming steps of expiring assets
+    pub fn with_freshness(mut self, window: u64) -> Self {
+        self.freshness = window;
+        self
+    }
+
+    pub fn registry(&self) -> &dyn NullifierRegistry<E::Field> {
+        self.registry.as_ref()
+    }
+
+    // adds a new identity and returns its account index. identity that is
+    // already in the wallet is not added twice
+    pub fn add_account(&mut self, auth: Auth<E>) -> usize {
+        self.account_index(auth.address()).unwrap_or_else(|| {
+            self.accounts.push(Account::new(auth));
+            self.accounts.len() - 1
+        })
+    }
+
+    pub fn accounts(&self) -> &[Account<E>] {
+        &self.accounts
+    }
+
+    pub fn account_index(&self, address: &Address<E::Field>) -> Option<usize> {
+        self.accounts
+            .iter()
+            .position(|account| account.address() == address)
+    }
+
+    fn account(&self, account: usize) -> Result<&Account<E>, crate::Error> {
+        self.accounts
+            .get(account)
+            .ok_or(crate::Error::BadAccountIndex(account))
+    }
+
+    // verify the proof chain starting from the issuance and return the last state
+    // and time. all proofs of the history, merged chains included, are checked in
+    // a single batch
+    fn verify_steps(
+        &self,
+        asset: &Asset<E::Field>,
+        steps: &[IVCStep<E>],
+    ) -> Result<(StateHash<E::Field>, u64), crate::Error> {
+        let mut batch = vec![];
+        let (state, time) = self.chain_steps(asset, None, vec![], steps, &mut batch)?;
+        let instances = batch
+            .iter()
+            .map(|(_, proof, public_input)| (*proof, public_input))
+            .collect::<Vec<_>>();
+        match self
+            .verifier
+            .verify_batch(&instances, &mut rand::thread_rng())?
+        {
+            Some(k) => Err(crate::Error::VerificationFailed {
+                step: batch[k].0.clone(),
+            }),
+            None => Ok((state, time)),
+        }
+    }
+
+    // check the history against the registry and record its spends. spends must
+    // agree with each other and with known spends, issuances must be published
+    // links of the supply chain
+    fn register_steps(
+        &mut self,
+        asset_hash: &AssetHash<E::Field>,
+        steps: &[IVCStep<E>],
+    ) -> Result<(), crate::Error> {
+        let spends = registry::spends(steps);
+        let nullifiers = spends
+            .iter()
+            .map(|(nullifier, _)| *nullifier)
+            .collect::<Vec<_>>();
+        let known = self.registry.get_batch(&nullifiers)?;
+        let mut seen = BTreeMap::new();
+        for ((nullifier, spend), known) in spends.iter().zip(known) {
+            let first = *seen.entry(nullifier).or_insert(known.unwrap_or(*spend));
+            (first == *spend)
+                .then_some(())
+                .ok_or(crate::Error::DoubleSpend)?;
+        }
+        for issuance in registry::issuances(steps) {
+            (self
+                .registry
+                .supply_link(asset_hash, issuance.supply_in())?
+                == Some(*issuance.supply_out()))
+            .then_some(())
+            .ok_or(crate::Error::SupplyConflict)?;
+        }
+        for (nullifier, spend) in spends.iter() {
+            self.registry.insert(nullifier, spend)?;
+        }
+        Ok(())
+    }
+
+    // walk the chain that continues `prefix`, the output states and times of the
+    // steps before, collect proofs with their public inputs and return the last
+    // state and time. second inputs of merge steps are chains of their own that
+    // share a prefix with the chain they are merged into, shared steps are only
+    // checked once. time of steps never goes backwards. `merged_at` is the merge
+    // step a merged chain is entered from, steps are reported by their path
+    #[allow(clippy::type_complexity)]
+    fn chain_steps<'a>(
+        &self,
+        asset: &Asset<E::Field>,
+        merged_at: Option<&StepPath>,
+        mut prefix: Vec<(StateHash<E::Field>, u64)>,
+        steps: &'a [IVCStep<E>],
+        batch: &mut Vec<(
+            StepPath,
+            &'a <<E as IVC>::Snark as SNARK<E::Field>>::Proof,
+            PublicInput<E::Field>,
+        )>,
+    ) -> Result<(StateHash<E::Field>, u64), crate::Error> {
+        let asset_hash = &asset.hash(&self.h);
+        let start = (asset_hash.as_ref().into(), 0);
+        for step in steps.iter() {
+            let i = prefix.len();
+            let path = merged_at.map_or(StepPath::at(i), |merge| merge.merged(i));
+            let (state_in, mut time) = prefix.last().copied().unwrap_or(start);
+            let mut public_input = PublicInput::new(
+                asset_hash,
+                &state_in,
+                &step.state,
+                i as u32,
+                &step.nullifier,
+            )
+            .with_time(step.time, asset.terms());
+            if let Some(issuance) = &step.issuance {
+                public_input = public_input.with_supply(issuance);
+            }
+            if let Some(merged) = &step.merged {
+                let shared = prefix
+                    .get(..merged.shared())
+                    .ok_or(crate::Error::BadMergedInput { step: path.clone() })?
+                    .to_vec();
+                let (state_in_1, time_1) =
+                    self.chain_steps(asset, Some(&path), shared, &merged.steps, batch)?;
+                public_input = public_input.with_merge(&state_in_1, &merged.nullifier);
+                time = time.max(time_1);
+            }
+            (step.time >= time)
+                .then_some(())
+                .ok_or(crate::Error::BadTimestamp { step: path.clone() })?;
+            batch.push((path, &step.proof, public_input));
+            prefix.push((step.state, step.time));
+        }
+        Ok(prefix.last().copied().unwrap_or(start))
+    }
+
+    pub fn issue<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        account: usize,
+        comm_receiver: &mut impl CommReceiver<E>,
+        asset: &Asset<E::Field>,
+        value: u64,
+    ) -> Result<(), crate::Error> {
+        let Account { auth, supply, .. } = self
+            .accounts
+            .get_mut(account)
+            .ok_or(crate::Error::BadAccountIndex(account))?;
+        (asset.issuer() == auth.address())
+            .then_some(())
+            .ok_or(crate::Error::NotIssuer)?;
+        let asset_hash = &asset.hash(&self.h);
+        let supply_in = supply.get(asset_hash).cloned().unwrap_or_default();
+        let issued = supply_in
+            .issued
+            .checked_add(value)
+            .ok_or(crate::Error::ValueOverflow)?;
+        if let Some(cap) = asset.terms().cap() {
+            (issued <= cap)
+                .then_some(())
+                .ok_or(crate::Error::ExceedsCap {
+                    cap,
+                    requested: value,
+                })?;
+        }
+        let now = (self.clock)();
+        // draw random blinding factor
+        let blind = Blind::<E::Field>::rand(rng);
+        // create new note
+        let note = Note::new(
+            asset_hash,
+            comm_receiver.address(),
+            value,
+            0,
+            &NoteOutIndex::Issue,
+            &crate::BlindNoteHash::default(),
+            blind,
+        );
+
+        // create the transaction
+        let tx = IssueTx::new(&note);
+        // and sign
+        let sealed = auth.issue(&self.h, &tx)?;
+
+        // open the supply commitment and commit to the supply after the issuance
+        let supply_blind = Blind::<E::Field>::rand(rng);
+        let issuance = Issuance::new(
+            &supply_in.commitment(&self.h, asset_hash),
+            &self.h.supply(asset_hash, issued, &supply_blind),
+        );
+
+        // construct public inputs
+        let state_in = &asset_hash.as_ref().into();
+        let state_out = &self.h.state_out_from_issue_tx(sealed.tx());
+
+        let public_inputs =
+            PublicInput::new(asset_hash, state_in, state_out, 0, &Default::default())
+                .with_time(now, asset.terms())
+                .with_supply(&issuance);
+
+        // contruct aux inputs, issued note is the only output
+        let public_key = auth.public_key();
+        let signature = sealed.signature();
+        let nullifier_key = auth.nullifier_key();
+        let aux_inputs: AuxInputs<E> = AuxInputs::new(asset, public_key, signature, nullifier_key)
+            .with_outputs(&[NoteOutput::new(&note)])
+            .with_supply(&SupplyOpening::new(
+                supply_in.issued,
+                &supply_in.blind,
+                &supply_blind,
+            ));
+
+        // crate proof
+        let proof = self
+            .prover
+            .create_proof(&self.h, public_inputs, aux_inputs, rng)?;
+
+        // create note history
+        let step =
+            IVCStep::new(&proof, state_out, &Default::default(), now).with_issuance(&issuance);
+        let (_, blind_note_hash) = self.h.note(&note);
+        let note_history = NoteHistory {
+            asset: *asset,
+            steps: vec![step],
+            current_note: note,
+            path: self.h.state_path(&[blind_note_hash], 0),
+        };
+
+        // publish the supply link and advance the ledger before the note leaves
+        self.registry
+            .publish_supply(asset_hash, issuance.supply_in(), issuance.supply_out())?;
+        let supply = supply.entry(*asset_hash).or_default();
+        supply.issued = issued;
+        supply.blind = supply_blind;
+
+        // send the new history to the receivers
+        comm_receiver.receive(&note_history)?;
+
+        Ok(())
+    }
+
+    pub fn split<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        account: usize,
+        comm_receiver: &mut impl CommReceiver<E>,
+        spendable_index: usize,
+        value: u64,
+    ) -> Result<(), crate::Error> {
+        self.split_many(rng, account, spendable_index, &mut [(comm_receiver, value)])
+    }
+
+    // pay several receivers in a single step. output 0 is the change note that
+    // stays in place of the spent note, output k is sent to the k-th receiver
+    pub fn split_many<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        account: usize,
+        spendable_index: usize,
+        payouts: &mut [(&mut dyn CommReceiver<E>, u64)],
+    ) -> Result<(), crate::Error> {
+        (payouts.len() < MAX_OUTPUTS)
+            .then_some(())
+            .ok_or(crate::Error::TooManyOutputs(payouts.len()))?;
+
+        let Account {
+            auth, spendables, ..
+        } = self
+            .accounts
+            .get_mut(account)
+            .ok_or(crate::Error::BadAccountIndex(account))?;
+        let sender = *auth.address();
+        let note_history = spendables
+            .get_mut(spendable_index)
+            .ok_or(crate::Error::BadSpendableIndex(spendable_index))?;
+
+        let note_in = note_history.current_note;
+        let step = note_history.steps.len() as u32;
+        let asset_hash = &note_history.asset.hash(&self.h);
+        let terms = note_history.asset.terms();
+        let (_, parent) = self.h.note(&note_in);
+        let now = (self.clock)();
+        (!terms.is_expired(now))
+            .then_some(())
+            .ok_or(crate::Error::Expired {
+                expiry: terms.expiry(),
+            })?;
+        // vouchers are only spent at their merchant
+        payouts
+            .iter()
+            .all(|(receiver, _)| terms.accepts(receiver.address()))
+            .then_some(())
+            .ok_or(crate::Error::MerchantRestricted)?;
+
+        // find output values
+        let requested = payouts
+            .iter()
+            .try_fold(0u64, |sum, (_, value)| sum.checked_add(*value))
+            .ok_or(crate::Error::ValueOverflow)?;
+        let change =
+            note_in
+                .value
+                .checked_sub(requested)
+                .ok_or(crate::Error::InsufficientFunds {
+                    available: note_in.value,
+                    requested,
+                })?;
+
+        // create change note, output 0, and transfer notes
+        let recipients = std::iter::once((sender, change)).chain(
+            payouts
+                .iter()
+                .map(|(receiver, value)| (*receiver.address(), *value)),
+        );
+        let notes_out = recipients
+            .enumerate()
+            .map(|(k, (owner, value))| {
+                Ok(Note::new(
+                    asset_hash,
+                    &owner,
+                    value,
+                    step,
+                    &NoteOutIndex::out(k)?,
+                    &parent,
+                    Blind::rand(rng),
+                ))
+            })
+            .collect::<Result<Vec<_>, crate::Error>>()?;
+
+        // create the transaction
+        let tx = SplitTx::new(&note_in, &notes_out);
+        // and sign and generate the nullifier
+        let sealed = auth.split(&self.h, &tx)?;
+
+        // construct public inputs
+        let state_in = &note_history.state(&self.h);
+        let leaves = self.h.leaves(sealed.notes_out());
+        let state_out = &self.h.state_out_from_split_tx(&tx);
+
+        let public_inputs =
+            PublicInput::new(asset_hash, state_in, state_out, step, sealed.nullifier())
+                .with_time(now, terms);
+
+        let outputs = notes_out.iter().map(NoteOutput::new).collect::<Vec<_>>();
+        let aux_inputs: AuxInputs<E> = AuxInputs::new(
+            &note_history.asset,
+            auth.public_key(),
+            sealed.signature(),
+            auth.nullifier_key(),
+        )
+        .with_inputs(&[NoteOpening::new(&note_in, &note_history.path)])
+        .with_outputs(&outputs);
+
+        // crate proof
+        let proof = self
+            .prover
+            .create_proof(&self.h, public_inputs, aux_inputs, rng)?;
+
+        // add the new step
+        let step = IVCStep::new(&proof, state_out, sealed.nullifier(), now);
+        let mut change = note_history.clone();
+        change.steps.push(step);
+
+        // transfer notes along with the shared history
+        let sent = (1..notes_out.len())
+            .map(|k| {
+                let mut history = change.clone();
+                history.current_note = notes_out[k];
+                history.path = self.h.state_path(&leaves, k);
+                history
+            })
+            .collect::<Vec<_>>();
+
+        // spend the note before anything leaves the wallet. change note takes its
+        // place, a zero change note is dropped
+        self.registry.insert(sealed.nullifier(), state_out)?;
+        change.current_note = notes_out[0];
+        change.path = self.h.state_path(&leaves, 0);
+        if change.value() == 0 {
+            spendables.remove(spendable_index);
+        } else {
+            *note_history = change;
+        }
+
+        for ((receiver, _), history) in payouts.iter_mut().zip(sent.iter()) {
+            receiver.receive(history)?;
+        }
+
+        Ok(())
+    }
+
+    // join two notes of the same asset into a single note. merged note takes
+    // the place of the first input and the second input is removed
+    pub fn merge<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        account: usize,
+        index_0: usize,
+        index_1: usize,
+    ) -> Result<(), crate::Error> {
+        let Account {
+            auth, spendables, ..
+        } = self
+            .accounts
+            .get_mut(account)
+            .ok_or(crate::Error::BadAccountIndex(account))?;
+        let sender = *auth.address();
+
+        (index_0 != index_1)
+            .then_some(())
+            .ok_or(crate::Error::BadSpendableIndex(index_1))?;
+        let history_0 = spendables
+            .get(index_0)
+            .ok_or(crate::Error::BadSpendableIndex(index_0))?;
+        let history_1 = spendables
+            .get(index_1)
+            .ok_or(crate::Error::BadSpendableIndex(index_1))?;
+        let asset_hash = &history_0.asset.hash(&self.h);
+        (*asset_hash == history_1.asset.hash(&self.h))
+            .then_some(())
+            .ok_or(crate::Error::AssetMismatch)?;
+
+        let note_in_0 = history_0.current_note;
+        let note_in_1 = history_1.current_note;
+        let step = history_0.steps.len() as u32;
+        let terms = history_0.asset.terms();
+        let now = (self.clock)();
+        (!terms.is_expired(now))
+            .then_some(())
+            .ok_or(crate::Error::Expired {
+                expiry: terms.expiry(),
+            })?;
+        let value = note_in_0
+            .value
+            .checked_add(note_in_1.value)
+            .ok_or(crate::Error::ValueOverflow)?;
+        (merge_depth(&history_1.steps) < MAX_MERGE_DEPTH)
+            .then_some(())
+            .ok_or(crate::Error::MergeTooDeep)?;
+
+        // create the merged note, the only output
+        let (_, parent) = self.h.note(&note_in_0);
+        let note_out = Note::new(
+            asset_hash,
+            &sender,
+            value,
+            step,
+            &NoteOutIndex::FIRST_OUT,
+            &parent,
+            Blind::rand(rng),
+        );
+
+        // create the transaction
+        let tx = MergeTx::new(&note_in_0, &note_in_1, &note_out);
+        // and sign and generate nullifiers
+        let sealed = auth.merge(&self.h, &tx)?;
+
+        // construct public inputs
+        let state_in_0 = &history_0.state(&self.h);
+        let state_in_1 = &history_1.state(&self.h);
+        let (_, blind_note_hash) = self.h.note(sealed.note_out());
+        let state_out = &self.h.state_out_from_merge_tx(&tx);
+
+        let public_inputs = PublicInput::new(
+            asset_hash,
+            state_in_0,
+            state_out,
+            step,
+            sealed.nullifier_0(),
+        )
+        .with_merge(state_in_1, sealed.nullifier_1())
+        .with_time(now, terms);
+
+        // construct aux inputs
+        let aux_inputs: AuxInputs<E> = AuxInputs::new(
+            &history_0.asset,
+            auth.public_key(),
+            sealed.signature(),
+            auth.nullifier_key(),
+        )
+        .with_inputs(&[
+            NoteOpening::new(&note_in_0, &history_0.path),
+            NoteOpening::new(&note_in_1, &history_1.path),
+        ])
+        .with_outputs(&[NoteOutput::new(&note_out)]);
+
+        // crate proof
+        let proof = self
+            .prover
+            .create_proof(&self.h, public_inputs, aux_inputs, rng)?;
+
+        // merged history extends the first input and carries the second one
+        let merged = MergedInput::new(sealed.nullifier_1(), &history_0.steps, &history_1.steps);
+        let step = IVCStep::new(&proof, state_out, sealed.nullifier_0(), now).with_merge(merged);
+        let mut note_history = history_0.clone();
+        note_history.steps.push(step);
+        note_history.current_note = note_out;
+        note_history.path = self.h.state_path(&[blind_note_hash], 0);
+
+        self.registry.insert(sealed.nullifier_0(), state_out)?;
+        self.registry.insert(sealed.nullifier_1(), state_out)?;
+        spendables[index_0] = note_history;
+        spendables.remove(index_1);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CommReceiver, Wallet};
+    use crate::asset::{Asset, Terms};
+    use crate::circuit::setup;
+    use crate::id::Auth;
+    use crate::poseidon::PoseidonConfigs;
+    use crate::test::Bls;
+    use crate::wallet::registry::MemoryRegistry;
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type F = ark_bls12_381::Fr;
+
+    #[test]
+    fn test_shared_registry() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let h = PoseidonConfigs::<F>::generate();
+        let shared = Rc::new(RefCell::new(MemoryRegistry::<F>::new()));
+        let mut wallet = |registry: Rc<RefCell<MemoryRegistry<F>>>| {
+            let auth = Auth::<Bls>::generate(&h, &mut rng).unwrap();
+            let (prover, verifier) = setup::<Bls, _>(&h, &mut rng).unwrap();
+            Wallet::new(auth, &h, prover, verifier, registry)
+        };
+        let mut issuer = wallet(shared.clone());
+        let mut alice = wallet(shared.clone());
+        let mut bob = wallet(Default::default());
+
+        let asset = Asset::new(CommReceiver::address(&issuer), &Terms::iou(10, 1));
+        issuer.issue(&mut rng, 0, &mut alice, &asset, 10).unwrap();
+        assert_eq!(alice.accounts[0].spendables[0].value(), 10);
+
+        // the issuance is not published to the registry bob uses
+        assert!(matches!(
+            issuer.issue(&mut rng, 0, &mut bob, &asset, 10),
+            Err(crate::Error::SupplyConflict)
+        ));
+        assert!(bob.accounts[0].spendables.is_empty());
+    }
+}
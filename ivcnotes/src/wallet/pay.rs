@@ -0,0 +1,215 @@
+use super::{CommReceiver, Wallet};
+use crate::{circuit::IVC, AssetHash};
+use rand::{CryptoRng, RngCore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+// how notes are picked to cover a payment. ties are always broken by the
+// spendable index, so the note received earlier is picked first
+pub enum CoinSelection {
+    // spend largest notes first
+    #[default]
+    LargestFirst,
+    // spend the smallest single note that covers the amount,
+    // otherwise fall back to largest first
+    FewestNotes,
+    // spend notes whose last step is the oldest first
+    OldestFirst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// note spent in a payment
+pub struct ConsumedNote {
+    // spendable index in the paying account before the payment
+    pub index: usize,
+    // value of the note before the payment
+    pub value: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Payment {
+    // spent notes, merged into the first one before the split
+    pub consumed: Vec<ConsumedNote>,
+    // value sent to the receiver
+    pub sent: u64,
+}
+
+impl Payment {
+    // value left in the paying account from consumed notes
+    pub fn change(&self) -> u64 {
+        self.consumed.iter().map(|note| note.value).sum::<u64>() - self.sent
+    }
+}
+
+impl CoinSelection {
+    // candidates are `(index, value, time of the last step)`
+    fn select(&self, mut candidates: Vec<(usize, u64, u64)>, amount: u64) -> Vec<(usize, u64)> {
+        match self {
+            CoinSelection::LargestFirst => {
+                candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            }
+            CoinSelection::FewestNotes => {
+                let single = candidates
+                    .iter()
+                    .filter(|(_, value, _)| *value >= amount)
+                    .min_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)))
+                    .copied();
+                if let Some((index, value, _)) = single {
+                    return vec![(index, value)];
+                }
+                candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            }
+            CoinSelection::OldestFirst => {
+                candidates.sort_by(|a, b| a.2.cmp(&b.2).then(a.0.cmp(&b.0)));
+            }
+        }
+        let mut sum = 0u64;
+        candidates
+            .into_iter()
+            .take_while(|(_, value, _)| {
+                let take = sum < amount;
+                sum = sum.saturating_add(*value);
+                take
+            })
+            .map(|(index, value, _)| (index, value))
+            .collect()
+    }
+}
+
+impl<E: IVC> Wallet<E> {
+    // pays `amount` of the asset from the account. when no single note covers the
+    // amount selected notes are merged into one first, so the receiver always gets
+    // a single note. merges done before a failure are kept. paying nothing spends
+    // no note, expired notes and vouchers of other merchants are never picked
+    pub fn pay<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        account: usize,
+        comm_receiver: &mut impl CommReceiver<E>,
+        asset_hash: &AssetHash<E::Field>,
+        amount: u64,
+        strategy: CoinSelection,
+    ) -> Result<Payment, crate::Error> {
+        if amount == 0 {
+            return Ok(Payment::default());
+        }
+        let now = (self.clock)();
+        let receiver = comm_receiver.address();
+        let candidates = self
+            .account(account)?
+            .spendables
+            .iter()
+            .enumerate()
+            .filter(|(_, history)| {
+                let terms = history.asset.terms();
+                history.asset.hash(&self.h) == *asset_hash
+                    && history.value() > 0
+                    && !terms.is_expired(now)
+                    && terms.accepts(receiver)
+            })
+            .map(|(index, history)| {
+                let time = history.steps.last().map_or(0, |step| step.time);
+                (index, history.value(), time)
+            })
+            .collect::<Vec<_>>();
+
+        let available = candidates
+            .iter()
+            .fold(0u64, |sum, (_, value, _)| sum.saturating_add(*value));
+        (available >= amount)
+            .then_some(())
+            .ok_or(crate::Error::InsufficientFunds {
+                available,
+                requested: amount,
+            })?;
+
+        let consumed = strategy
+            .select(candidates, amount)
+            .into_iter()
+            .map(|(index, value)| ConsumedNote { index, value })
+            .collect::<Vec<_>>();
+
+        // merge into the lowest index, removing higher indexes first keeps the
+        // remaining ones in place
+        let mut indexes = consumed.iter().map(|note| note.index).collect::<Vec<_>>();
+        indexes.sort_unstable();
+        let (target, rest) = indexes
+            .split_first()
+            .ok_or(crate::Error::InsufficientFunds {
+                available,
+                requested: amount,
+            })?;
+        for index in rest.iter().rev() {
+            self.merge(rng, account, *target, *index)?;
+        }
+        self.split(rng, account, comm_receiver, *target, amount)?;
+
+        Ok(Payment {
+            consumed,
+            sent: amount,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CoinSelection, Payment};
+    use crate::asset::{Asset, Terms};
+    use crate::circuit::setup;
+    use crate::id::Auth;
+    use crate::poseidon::PoseidonConfigs;
+    use crate::test::Bls;
+    use crate::wallet::registry::MemoryRegistry;
+    use crate::wallet::{CommReceiver, Wallet};
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type F = ark_bls12_381::Fr;
+
+    #[test]
+    fn test_pay_candidates() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let h = PoseidonConfigs::<F>::generate();
+        let shared = Rc::new(RefCell::new(MemoryRegistry::<F>::new()));
+        let mut wallet = || {
+            let auth = Auth::<Bls>::generate(&h, &mut rng).unwrap();
+            let (prover, verifier) = setup::<Bls, _>(&h, &mut rng).unwrap();
+            Wallet::new(auth, &h, prover, verifier, shared.clone()).with_clock(|| 100)
+        };
+        let mut issuer = wallet();
+        let mut alice = wallet();
+        let mut bob = wallet();
+        let mut merchant = wallet();
+
+        let voucher = Terms::voucher(200, CommReceiver::address(&merchant), 1);
+        let voucher = Asset::new(CommReceiver::address(&issuer), &voucher);
+        issuer.issue(&mut rng, 0, &mut alice, &voucher, 10).unwrap();
+        let voucher = voucher.hash(&h);
+        let strategy = CoinSelection::LargestFirst;
+
+        // nothing to pay spends nothing
+        let payment = alice
+            .pay(&mut rng, 0, &mut bob, &voucher, 0, strategy)
+            .unwrap();
+        assert_eq!(payment, Payment::default());
+        assert_eq!(alice.accounts[0].spendables[0].value(), 10);
+
+        // the voucher is not spendable at bob
+        assert!(matches!(
+            alice.pay(&mut rng, 0, &mut bob, &voucher, 4, strategy),
+            Err(crate::Error::InsufficientFunds { available: 0, .. })
+        ));
+        alice
+            .pay(&mut rng, 0, &mut merchant, &voucher, 4, strategy)
+            .unwrap();
+        assert_eq!(merchant.accounts[0].spendables[0].value(), 4);
+
+        // nor anywhere after it expires
+        let mut alice = alice.with_clock(|| 300);
+        let mut merchant = merchant.with_clock(|| 300);
+        assert!(matches!(
+            alice.pay(&mut rng, 0, &mut merchant, &voucher, 4, strategy),
+            Err(crate::Error::InsufficientFunds { available: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,241 @@
+use super::Wallet;
+use crate::{
+    asset::{Asset, TermsKind},
+    circuit::IVC,
+    Address, AssetHash,
+};
+use ark_ff::PrimeField;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy)]
+// unspent note of a wallet account
+pub struct SpendableNote<F: PrimeField> {
+    // account that owns the note
+    pub account: usize,
+    // spendable index in the account, to be used with `Wallet::split`
+    pub index: usize,
+    pub asset: Asset<F>,
+    pub value: u64,
+    // number of transactions from the issuance
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+// total value held of a single asset
+pub struct AssetBalance<F: PrimeField> {
+    pub asset: Asset<F>,
+    pub value: u64,
+    // number of notes the value is spread over
+    pub notes: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// what the holder can do with an asset at a given time
+pub enum AssetStatus {
+    // transferable, not redeemable before maturity
+    Pending,
+    // transferable and redeemable
+    Redeemable,
+    // past expiry, can only be redeemed
+    Expired,
+}
+
+#[derive(Debug, Clone, Copy)]
+// balance of a single asset at a given time
+pub struct AssetStatement<F: PrimeField> {
+    pub balance: AssetBalance<F>,
+    pub kind: TermsKind,
+    pub status: AssetStatus,
+    // coupon interest accrued on the balance, bonds only
+    pub accrued: u64,
+}
+
+impl<E: IVC> Wallet<E> {
+    // unspent notes of all accounts
+    pub fn spendable_notes(&self) -> Vec<SpendableNote<E::Field>> {
+        self.accounts
+            .iter()
+            .enumerate()
+            .flat_map(|(account, acc)| {
+                acc.spendables
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, history)| SpendableNote {
+                        account,
+                        index,
+                        asset: *history.asset(),
+                        value: history.value(),
+                        depth: history.depth(),
+                    })
+            })
+            .collect()
+    }
+
+    // unspent notes whose asset is matured at `now`
+    pub fn matured_notes(&self, now: u64) -> Vec<SpendableNote<E::Field>> {
+        self.spendable_notes()
+            .into_iter()
+            .filter(|note| note.asset.terms().is_matured(now))
+            .collect()
+    }
+
+    // total value per asset. totals saturate at `u64::MAX`
+    pub fn balances(&self) -> BTreeMap<AssetHash<E::Field>, u64> {
+        self.portfolio()
+            .into_iter()
+            .map(|balance| (balance.asset.hash(&self.h), balance.value))
+            .collect()
+    }
+
+    // value per asset ordered by issuer and then by terms
+    pub fn portfolio(&self) -> Vec<AssetBalance<E::Field>> {
+        let mut portfolio = BTreeMap::<AssetHash<E::Field>, AssetBalance<E::Field>>::new();
+        for note in self.spendable_notes() {
+            portfolio
+                .entry(note.asset.hash(&self.h))
+                .and_modify(|balance| {
+                    balance.value = balance.value.saturating_add(note.value);
+                    balance.notes += 1;
+                })
+                .or_insert(AssetBalance {
+                    asset: note.asset,
+                    value: note.value,
+                    notes: 1,
+                });
+        }
+        let mut portfolio = portfolio.into_values().collect::<Vec<_>>();
+        portfolio.sort_by(|a, b| {
+            (a.asset.issuer(), a.asset.terms()).cmp(&(b.asset.issuer(), b.asset.terms()))
+        });
+        portfolio
+    }
+
+    // portfolio split by issuer
+    pub fn portfolio_by_issuer(&self) -> BTreeMap<Address<E::Field>, Vec<AssetBalance<E::Field>>> {
+        let mut by_issuer = BTreeMap::<_, Vec<_>>::new();
+        for balance in self.portfolio() {
+            by_issuer
+                .entry(*balance.asset.issuer())
+                .or_default()
+                .push(balance);
+        }
+        by_issuer
+    }
+
+    // portfolio split by kind of terms
+    pub fn portfolio_by_kind(&self) -> BTreeMap<TermsKind, Vec<AssetBalance<E::Field>>> {
+        let mut by_kind = BTreeMap::<_, Vec<_>>::new();
+        for balance in self.portfolio() {
+            by_kind
+                .entry(balance.asset.terms().kind())
+                .or_default()
+                .push(balance);
+        }
+        by_kind
+    }
+
+    // portfolio with the status and accrued interest of each asset at `now`
+    pub fn statement(&self, now: u64) -> Vec<AssetStatement<E::Field>> {
+        self.portfolio()
+            .into_iter()
+            .map(|balance| {
+                let terms = balance.asset.terms();
+                let status = if terms.is_expired(now) {
+                    AssetStatus::Expired
+                } else if terms.is_matured(now) {
+                    AssetStatus::Redeemable
+                } else {
+                    AssetStatus::Pending
+                };
+                AssetStatement {
+                    balance,
+                    kind: terms.kind(),
+                    status,
+                    accrued: terms.accrued(balance.value, now),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AssetStatus;
+    use crate::asset::{Asset, Terms, TermsKind};
+    use crate::circuit::setup;
+    use crate::id::Auth;
+    use crate::poseidon::PoseidonConfigs;
+    use crate::test::Bls;
+    use crate::wallet::registry::MemoryRegistry;
+    use crate::wallet::{CommReceiver, Wallet};
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type F = ark_bls12_381::Fr;
+
+    #[test]
+    fn test_portfolio_totals() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let h = PoseidonConfigs::<F>::generate();
+        let shared = Rc::new(RefCell::new(MemoryRegistry::<F>::new()));
+        let mut wallet = || {
+            let auth = Auth::<Bls>::generate(&h, &mut rng).unwrap();
+            let (prover, verifier) = setup::<Bls, _>(&h, &mut rng).unwrap();
+            Wallet::new(auth, &h, prover, verifier, shared.clone()).with_clock(|| 100)
+        };
+        let mut first = wallet();
+        let mut second = wallet();
+        let mut alice = wallet();
+
+        let iou = Asset::new(CommReceiver::address(&first), &Terms::iou(10, 1));
+        let bond = Terms::bond(0, 200, 50, 500, 1).unwrap();
+        let bond = Asset::new(CommReceiver::address(&first), &bond);
+        let other = Asset::new(CommReceiver::address(&second), &Terms::iou(10, 1));
+        first.issue(&mut rng, 0, &mut alice, &iou, 30).unwrap();
+        first.issue(&mut rng, 0, &mut alice, &iou, 20).unwrap();
+        first.issue(&mut rng, 0, &mut alice, &bond, 1000).unwrap();
+        second.issue(&mut rng, 0, &mut alice, &other, 5).unwrap();
+
+        assert_eq!(alice.spendable_notes().len(), 4);
+        let balances = alice.balances();
+        assert_eq!(balances.len(), 3);
+        assert_eq!(balances[&iou.hash(&h)], 50);
+        assert_eq!(balances[&bond.hash(&h)], 1000);
+        assert_eq!(balances[&other.hash(&h)], 5);
+
+        let portfolio = alice.portfolio();
+        let iou_balance = portfolio
+            .iter()
+            .find(|balance| balance.asset.hash(&h) == iou.hash(&h))
+            .unwrap();
+        assert_eq!((iou_balance.value, iou_balance.notes), (50, 2));
+
+        let by_issuer = alice.portfolio_by_issuer();
+        assert_eq!(by_issuer[CommReceiver::address(&first)].len(), 2);
+        assert_eq!(by_issuer[CommReceiver::address(&second)].len(), 1);
+        let by_kind = alice.portfolio_by_kind();
+        assert_eq!(by_kind[&TermsKind::IOU].len(), 2);
+        assert_eq!(by_kind[&TermsKind::Bond].len(), 1);
+
+        // two coupons of 5% are due before maturity, four after
+        let bond_statement = |now: u64| {
+            alice
+                .statement(now)
+                .into_iter()
+                .find(|statement| statement.balance.asset.hash(&h) == bond.hash(&h))
+                .map(|statement| (statement.status, statement.accrued))
+                .unwrap()
+        };
+        assert_eq!(bond_statement(100), (AssetStatus::Pending, 100));
+        assert_eq!(bond_statement(250), (AssetStatus::Redeemable, 200));
+        assert!(
+            alice
+                .statement(100)
+                .iter()
+                .filter(|statement| statement.kind == TermsKind::IOU)
+                .all(|statement| statement.status == AssetStatus::Redeemable
+                    && statement.accrued == 0)
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use super::{Account, Wallet};
+use crate::{
+    asset::Asset,
+    circuit::{
+        inputs::{AuxInputs, NoteOpening, PublicInput},
+        IVC,
+    },
+    deserialize_version,
+    id::{verify_signature, Auth},
+    note::{IVCStep, StepPath},
+    poseidon::PoseidonConfigs,
+    serialize_version,
+    tx::{RedeemTx, SealedRedeemTx},
+    Address, AssetHash, Blind, Nullifier, StateHash, SupplyHash,
+};
+use ark_crypto_primitives::sponge::Absorb;
+use ark_ff::PrimeField;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
+};
+use arkeddsa::{signature::Signature, PublicKey};
+use rand::{CryptoRng, RngCore};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+// issuer side ledger of an asset
+pub struct Supply<F: PrimeField> {
+    // total value issued so far
+    pub(crate) issued: u64,
+    // total value retired by redemptions
+    pub(crate) redeemed: u64,
+    // nullifiers of redeemed notes
+    pub(crate) redemptions: Vec<Nullifier<F>>,
+    // blinding factor of the issued supply commitment, zero before the first issuance
+    pub(crate) blind: Blind<F>,
+}
+
+impl<F: PrimeField> Supply<F> {
+    pub fn issued(&self) -> u64 {
+        self.issued
+    }
+
+    pub fn redeemed(&self) -> u64 {
+        self.redeemed
+    }
+
+    // value still in circulation
+    pub fn outstanding(&self) -> u64 {
+        self.issued - self.redeemed
+    }
+
+    pub fn is_redeemed(&self, nullifier: &Nullifier<F>) -> bool {
+        self.redemptions.contains(nullifier)
+    }
+
+    // commitment to the issued value, opened by the next issuance
+    pub fn commitment(&self, h: &PoseidonConfigs<F>, asset_hash: &AssetHash<F>) -> SupplyHash<F>
+    where
+        F: Absorb,
+    {
+        match self.blind == Blind::default() {
+            true => SupplyHash::default(),
+            false => h.supply(asset_hash, self.issued, &self.blind),
+        }
+    }
+}
+
+// note presented to its issuer for settlement
+pub struct Redemption<E: IVC> {
+    pub(crate) asset: Asset<E::Field>,
+    // proof chain of the redeemed note, last step is the redeem step
+    pub(crate) steps: Vec<IVCStep<E>>,
+    // value of the redeemed note
+    pub(crate) value: u64,
+}
+
+impl<E: IVC> Redemption<E> {
+    pub fn asset(&self) -> &Asset<E::Field> {
+        &self.asset
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+// issuer's signed acknowledgement of a redemption
+pub struct RedemptionReceipt<E: IVC> {
+    pub(crate) asset_hash: AssetHash<E::Field>,
+    pub(crate) nullifier: Nullifier<E::Field>,
+    pub(crate) value: u64,
+    pub(crate) signature: Signature<E::TE>,
+    // opening of the issuer address, public key and nullifier key commitment
+    pub(crate) public_key: PublicKey<E::TE>,
+    pub(crate) key_commitment: E::Field,
+}
+
+impl<E: IVC> RedemptionReceipt<E> {
+    // receipt is signed by the key behind the issuer address
+    pub fn verify(&self, h: &PoseidonConfigs<E::Field>, issuer: &Address<E::Field>) -> bool {
+        let sighash = h.sighash_receipt(&self.asset_hash, &self.nullifier, self.value);
+        h.id_opening(&self.key_commitment, &self.public_key) == *issuer
+            && verify_signature::<E>(&h.eddsa, &self.public_key, &self.signature, &sighash)
+    }
+
+    pub fn asset_hash(&self) -> &AssetHash<E::Field> {
+        &self.asset_hash
+    }
+
+    pub fn nullifier(&self) -> &Nullifier<E::Field> {
+        &self.nullifier
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    pub fn signature(&self) -> &Signature<E::TE> {
+        &self.signature
+    }
+}
+
+pub trait RedemptionReceiver<E: IVC> {
+    fn redeem(&mut self, redemption: &Redemption<E>) -> Result<RedemptionReceipt<E>, crate::Error>;
+}
+
+impl<E: IVC> Auth<E> {
+    // sign redeem transaction and generate the nullifier
+    pub(crate) fn redeem(
+        &self,
+        h: &PoseidonConfigs<E::Field>,
+        tx: &RedeemTx<E::Field>,
+    ) -> Result<SealedRedeemTx<E::TE>, crate::Error> {
+        let sighash = h.sighash_redeem_tx(tx);
+        let signature = self.sign(&sighash);
+        let (note_in, _) = h.note(&tx.note_in);
+        let nullifier = h.nullifier(&note_in, self.nullifier_key());
+        Ok(tx.seal(&signature, &nullifier))
+    }
+}
+
+// issuer accepts redemptions of assets issued by one of its accounts
+impl<E: IVC> RedemptionReceiver<E> for Wallet<E> {
+    fn redeem(&mut self, redemption: &Redemption<E>) -> Result<RedemptionReceipt<E>, crate::Error> {
+        let asset_hash = redemption.asset.hash(&self.h);
+        let account = self
+            .account_index(redemption.asset.issuer())
+            .ok_or(crate::Error::NotIssuer)?;
+
+        // verify the chain up to the redeemed note and then the redeem step
+        let (redeem_step, steps) =
+            redemption
+                .steps
+                .split_last()
+                .ok_or(crate::Error::VerificationFailed {
+                    step: StepPath::at(0),
+                })?;
+        let (state_in, time) = self.verify_steps(&redemption.asset, steps)?;
+        (time <= redeem_step.time && redeem_step.time <= (self.clock)())
+            .then_some(())
+            .ok_or(crate::Error::BadTimestamp {
+                step: StepPath::at(steps.len()),
+            })?;
+        let public_input = PublicInput::new(
+            &asset_hash,
+            &state_in,
+            &StateHash::default(),
+            steps.len() as u32,
+            &redeem_step.nullifier,
+        )
+        .with_redeem(redemption.value)
+        .with_time(redeem_step.time, redemption.asset.terms());
+        let verified = redeem_step.state == StateHash::default()
+            && self
+                .verifier
+                .verify_proof(&redeem_step.proof, &public_input)?;
+        verified
+            .then_some(())
+            .ok_or(crate::Error::VerificationFailed {
+                step: StepPath::at(steps.len()),
+            })?;
+
+        // match against the issued supply
+        let supply = self.accounts[account]
+            .supply
+            .get(&asset_hash)
+            .cloned()
+            .unwrap_or_default();
+        (!supply.is_redeemed(&redeem_step.nullifier))
+            .then_some(())
+            .ok_or(crate::Error::AlreadyRedeemed)?;
+        let redeemed = supply
+            .redeemed
+            .checked_add(redemption.value)
+            .filter(|redeemed| *redeemed <= supply.issued)
+            .ok_or(crate::Error::ExceedsSupply {
+                outstanding: supply.outstanding(),
+                requested: redemption.value,
+            })?;
+
+        // the chain must not double spend and the redeem nullifier is recorded
+        // as spent before the receipt is signed
+        self.register_steps(&asset_hash, &redemption.steps)?;
+
+        let Account { auth, supply, .. } = &mut self.accounts[account];
+        let supply = supply.entry(asset_hash).or_default();
+        supply.redeemed = redeemed;
+        supply.redemptions.push(redeem_step.nullifier);
+
+        let sighash = self
+            .h
+            .sighash_receipt(&asset_hash, &redeem_step.nullifier, redemption.value);
+        Ok(RedemptionReceipt {
+            asset_hash,
+            nullifier: redeem_step.nullifier,
+            value: redemption.value,
+            signature: auth.sign(&sighash),
+            public_key: auth.public_key().clone(),
+            key_commitment: auth.key_commitment(&self.h),
+        })
+    }
+}
+
+impl<E: IVC> Wallet<E> {
+    // issuer side ledger of the asset
+    pub fn supply(
+        &self,
+        account: usize,
+        asset_hash: &AssetHash<E::Field>,
+    ) -> Result<Option<&Supply<E::Field>>, crate::Error> {
+        Ok(self.account(account)?.supply.get(asset_hash))
+    }
+
+    // present the note to its issuer. note is removed once the issuer signs the receipt
+    pub fn redeem_note<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        account: usize,
+        issuer: &mut impl RedemptionReceiver<E>,
+        spendable_index: usize,
+    ) -> Result<RedemptionReceipt<E>, crate::Error> {
+        let Account {
+            auth, spendables, ..
+        } = self
+            .accounts
+            .get_mut(account)
+            .ok_or(crate::Error::BadAccountIndex(account))?;
+        let note_history = spendables
+            .get(spendable_index)
+            .ok_or(crate::Error::BadSpendableIndex(spendable_index))?;
+
+        let note_in = note_history.current_note;
+        let step = note_history.steps.len() as u32;
+        let asset_hash = &note_history.asset.hash(&self.h);
+        let terms = note_history.asset.terms();
+        let now = (self.clock)();
+        terms
+            .is_matured(now)
+            .then_some(())
+            .ok_or(crate::Error::NotMatured {
+                maturity: terms.maturity(),
+            })?;
+
+        // create the transaction
+        let tx = RedeemTx::new(&note_in);
+        // and sign and generate the nullifier
+        let sealed = auth.redeem(&self.h, &tx)?;
+
+        // construct public inputs, redeem has no outputs
+        let state_in = &note_history.state(&self.h);
+        let state_out = &StateHash::default();
+        let public_inputs =
+            PublicInput::new(asset_hash, state_in, state_out, step, sealed.nullifier())
+                .with_redeem(sealed.note_in().value)
+                .with_time(now, terms);
+
+        let aux_inputs: AuxInputs<E> = AuxInputs::new(
+            &note_history.asset,
+            auth.public_key(),
+            sealed.signature(),
+            auth.nullifier_key(),
+        )
+        .with_inputs(&[NoteOpening::new(&note_in, &note_history.path)]);
+
+        // crate proof
+        let proof = self
+            .prover
+            .create_proof(&self.h, public_inputs, aux_inputs, rng)?;
+
+        let mut steps = note_history.steps.clone();
+        steps.push(IVCStep::new(&proof, state_out, sealed.nullifier(), now));
+        let redemption = Redemption {
+            asset: note_history.asset,
+            steps,
+            value: note_in.value,
+        };
+
+        // note is kept until the issuer signs a receipt for exactly this redemption
+        let receipt = issuer.redeem(&redemption)?;
+        (receipt.asset_hash == *asset_hash
+            && receipt.nullifier == *sealed.nullifier()
+            && receipt.value == note_in.value
+            && receipt.verify(&self.h, note_history.asset.issuer()))
+        .then_some(())
+        .ok_or(crate::Error::BadReceipt)?;
+        spendables.remove(spendable_index);
+        // the issuer registered the spend before signing, the note is redeemed
+        // even if it can't be recorded here
+        let _ = self.registry.insert(sealed.nullifier(), state_out);
+
+        Ok(receipt)
+    }
+}
+
+impl<F: PrimeField> CanonicalSerialize for Supply<F> {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        serialize_version(&mut writer, compress)?;
+        self.issued.serialize_with_mode(&mut writer, compress)?;
+        self.redeemed.serialize_with_mode(&mut writer, compress)?;
+        self.redemptions
+            .serialize_with_mode(&mut writer, compress)?;
+        self.blind.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        1 + self.issued.serialized_size(compress)
+            + self.redeemed.serialized_size(compress)
+            + self.redemptions.serialized_size(compress)
+            + self.blind.serialized_size(compress)
+    }
+}
+
+impl<F: PrimeField> Valid for Supply<F> {
+    fn check(&self) -> Result<(), SerializationError> {
+        // cannot redeem more than issued
+        if self.redeemed > self.issued {
+            return Err(SerializationError::InvalidData);
+        }
+        self.redemptions.check()
+    }
+}
+
+impl<F: PrimeField> CanonicalDeserialize for Supply<F> {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        deserialize_version(&mut reader, compress)?;
+        let supply = Self {
+            issued: u64::deserialize_with_mode(&mut reader, compress, Validate::No)?,
+            redeemed: u64::deserialize_with_mode(&mut reader, compress, Validate::No)?,
+            redemptions: Vec::deserialize_with_mode(&mut reader, compress, Validate::No)?,
+            blind: Blind::deserialize_with_mode(&mut reader, compress, Validate::No)?,
+        };
+        if validate == Validate::Yes {
+            supply.check()?;
+        }
+        Ok(supply)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::asset::{Asset, Terms};
+    use crate::circuit::setup;
+    use crate::id::Auth;
+    use crate::poseidon::PoseidonConfigs;
+    use crate::test::Bls;
+    use crate::wallet::registry::MemoryRegistry;
+    use crate::wallet::{CommReceiver, Wallet};
+    use rand::SeedableRng;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type F = ark_bls12_381::Fr;
+
+    #[test]
+    fn test_redeem_note() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        let h = PoseidonConfigs::<F>::generate();
+        let shared = Rc::new(RefCell::new(MemoryRegistry::<F>::new()));
+        let mut wallet = || {
+            let auth = Auth::<Bls>::generate(&h, &mut rng).unwrap();
+            let (prover, verifier) = setup::<Bls, _>(&h, &mut rng).unwrap();
+            Wallet::new(auth, &h, prover, verifier, shared.clone())
+        };
+        let mut issuer = wallet();
+        let mut alice = wallet();
+        let mut bob = wallet();
+
+        let asset = Asset::new(CommReceiver::address(&issuer), &Terms::iou(10, 1));
+        issuer.issue(&mut rng, 0, &mut alice, &asset, 100).unwrap();
+        alice.split(&mut rng, 0, &mut bob, 0, 30).unwrap();
+
+        // the note is kept when the redemption is refused
+        assert!(alice.redeem_note(&mut rng, 0, &mut bob, 0).is_err());
+        assert_eq!(alice.accounts[0].spendables.len(), 1);
+
+        let receipt = bob.redeem_note(&mut rng, 0, &mut issuer, 0).unwrap();
+        assert_eq!(receipt.value(), 30);
+        assert!(receipt.verify(&h, CommReceiver::address(&issuer)));
+        assert!(bob.accounts[0].spendables.is_empty());
+        let supply = issuer.supply(0, &asset.hash(&h)).unwrap().unwrap();
+        assert_eq!((supply.issued(), supply.redeemed()), (100, 30));
+    }
+}
@@ -0,0 +1,506 @@
+use crate::{
+    circuit::IVC,
+    note::{IVCStep, Issuance},
+    AssetHash, Nullifier, StateHash, SupplyHash,
+};
+use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use sha2::Digest;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+// spent nullifiers known to a wallet. a nullifier is bound to the output state of
+// the step that spent it, so the same spend seen again through shared history is
+// accepted while a second spend of the note is not.
+//
+// the registry also holds the supply chain of each asset, the links from the
+// supply commitment an issuance opens to the one it commits to. issuers publish
+// every link and receivers only accept issuances that are published links, so
+// wallets that exchange notes share a registry, e.g. a relay
+pub trait NullifierRegistry<F: PrimeField> {
+    // output state of the step that spent the nullifier
+    fn get(&self, nullifier: &Nullifier<F>) -> Result<Option<StateHash<F>>, crate::Error>;
+
+    // record a spend, fails if the nullifier is spent by another step
+    fn insert(
+        &mut self,
+        nullifier: &Nullifier<F>,
+        spend: &StateHash<F>,
+    ) -> Result<(), crate::Error>;
+
+    // supply commitment published after `supply_in` in the chain of the asset
+    fn supply_link(
+        &self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+    ) -> Result<Option<SupplyHash<F>>, crate::Error>;
+
+    // extend the supply chain of the asset. `supply_in` must be the latest
+    // published commitment, or zero for the first issuance. publishing a known
+    // link again is accepted
+    fn publish_supply(
+        &mut self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+        supply_out: &SupplyHash<F>,
+    ) -> Result<(), crate::Error>;
+
+    fn contains(&self, nullifier: &Nullifier<F>) -> Result<bool, crate::Error> {
+        Ok(self.get(nullifier)?.is_some())
+    }
+
+    fn get_batch(
+        &self,
+        nullifiers: &[Nullifier<F>],
+    ) -> Result<Vec<Option<StateHash<F>>>, crate::Error> {
+        nullifiers
+            .iter()
+            .map(|nullifier| self.get(nullifier))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryRegistry<F: PrimeField> {
+    spends: BTreeMap<Nullifier<F>, StateHash<F>>,
+    // supply chain links keyed by the asset and the opened commitment
+    links: BTreeMap<(AssetHash<F>, SupplyHash<F>), SupplyHash<F>>,
+    // latest published supply commitment of each asset
+    heads: BTreeMap<AssetHash<F>, SupplyHash<F>>,
+}
+
+impl<F: PrimeField> MemoryRegistry<F> {
+    pub fn new() -> Self {
+        Self {
+            spends: BTreeMap::new(),
+            links: BTreeMap::new(),
+            heads: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.spends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spends.is_empty()
+    }
+
+    // whether the link is new, fails if it doesn't extend the chain
+    fn extends_supply(
+        &self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+        supply_out: &SupplyHash<F>,
+    ) -> Result<bool, crate::Error> {
+        match self.links.get(&(*asset_hash, *supply_in)) {
+            Some(known) if known == supply_out => Ok(false),
+            Some(_) => Err(crate::Error::SupplyConflict),
+            None => {
+                // zero is the head before the first issuance. a link back into
+                // the chain would make it loop
+                let head = self.heads.get(asset_hash).copied().unwrap_or_default();
+                (head == *supply_in
+                    && *supply_out != SupplyHash::default()
+                    && !self.links.contains_key(&(*asset_hash, *supply_out)))
+                .then_some(true)
+                .ok_or(crate::Error::SupplyConflict)
+            }
+        }
+    }
+}
+
+impl<F: PrimeField> NullifierRegistry<F> for MemoryRegistry<F> {
+    fn get(&self, nullifier: &Nullifier<F>) -> Result<Option<StateHash<F>>, crate::Error> {
+        Ok(self.spends.get(nullifier).copied())
+    }
+
+    fn insert(
+        &mut self,
+        nullifier: &Nullifier<F>,
+        spend: &StateHash<F>,
+    ) -> Result<(), crate::Error> {
+        match self.spends.get(nullifier) {
+            Some(known) if known != spend => Err(crate::Error::DoubleSpend),
+            Some(_) => Ok(()),
+            None => {
+                self.spends.insert(*nullifier, *spend);
+                Ok(())
+            }
+        }
+    }
+
+    fn supply_link(
+        &self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+    ) -> Result<Option<SupplyHash<F>>, crate::Error> {
+        Ok(self.links.get(&(*asset_hash, *supply_in)).copied())
+    }
+
+    fn publish_supply(
+        &mut self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+        supply_out: &SupplyHash<F>,
+    ) -> Result<(), crate::Error> {
+        if self.extends_supply(asset_hash, supply_in, supply_out)? {
+            self.links.insert((*asset_hash, *supply_in), *supply_out);
+            self.heads.insert(*asset_hash, *supply_out);
+        }
+        Ok(())
+    }
+}
+
+const RECORD_SPEND: u8 = 0;
+const RECORD_SUPPLY: u8 = 1;
+// records are framed by a little endian u32 length and a checksum of the body
+const RECORD_HEADER: usize = 4 + 4;
+
+fn checksum(body: &[u8]) -> [u8; 4] {
+    let digest = sha2::Sha256::digest(body);
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+// append-only log of spends and supply links. the log is replayed into memory
+// when opened and every new record is synced to disk before it is accepted. a
+// crash while appending leaves a torn last record, it is cut off when the log is
+// opened again. a bad record anywhere else is corruption
+pub struct FileRegistry<F: PrimeField> {
+    file: File,
+    spends: MemoryRegistry<F>,
+}
+
+impl<F: PrimeField> FileRegistry<F> {
+    // open the log at `path`, creating an empty one if it does not exist
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, crate::Error> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+
+        let mut spends = MemoryRegistry::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let Some(body) = Self::framed(&bytes[offset..]) else {
+                // only the last record may be torn, some filesystems leave it zeroed
+                let torn = Self::framed_len(&bytes[offset..])
+                    .map_or(true, |len| offset + len >= bytes.len())
+                    || bytes[offset..].iter().all(|byte| *byte == 0);
+                torn.then_some(())
+                    .ok_or(ark_serialize::SerializationError::InvalidData)?;
+                file.set_len(offset as u64)?;
+                file.sync_data()?;
+                break;
+            };
+            Self::replay(&mut spends, body)?;
+            offset += RECORD_HEADER + body.len();
+        }
+        Ok(Self { file, spends })
+    }
+
+    // length of the record at the start of `bytes` if its header is complete
+    fn framed_len(bytes: &[u8]) -> Option<usize> {
+        let len = bytes.get(..4)?;
+        Some(RECORD_HEADER + u32::from_le_bytes(len.try_into().unwrap()) as usize)
+    }
+
+    // body of the record at the start of `bytes`, `None` if it is short or its
+    // checksum doesn't match
+    fn framed(bytes: &[u8]) -> Option<&[u8]> {
+        let body = bytes.get(RECORD_HEADER..Self::framed_len(bytes)?)?;
+        (bytes[4..RECORD_HEADER] == checksum(body)).then_some(body)
+    }
+
+    fn replay(spends: &mut MemoryRegistry<F>, mut reader: &[u8]) -> Result<(), crate::Error> {
+        match u8::deserialize_compressed(&mut reader)? {
+            RECORD_SPEND => {
+                let nullifier = Nullifier::deserialize_compressed(&mut reader)?;
+                let spend = StateHash::deserialize_compressed(&mut reader)?;
+                spends.insert(&nullifier, &spend)
+            }
+            RECORD_SUPPLY => {
+                let asset_hash = AssetHash::deserialize_compressed(&mut reader)?;
+                let supply_in = SupplyHash::deserialize_compressed(&mut reader)?;
+                let supply_out = SupplyHash::deserialize_compressed(&mut reader)?;
+                spends.publish_supply(&asset_hash, &supply_in, &supply_out)
+            }
+            _ => Err(ark_serialize::SerializationError::InvalidData.into()),
+        }
+    }
+
+    fn append(&mut self, body: &[u8]) -> Result<(), crate::Error> {
+        let len = u32::try_from(body.len())
+            .map_err(|_| ark_serialize::SerializationError::InvalidData)?;
+        let mut record = len.to_le_bytes().to_vec();
+        record.extend(checksum(body));
+        record.extend(body);
+        self.file.write_all(&record)?;
+        Ok(self.file.sync_data()?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.spends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spends.is_empty()
+    }
+}
+
+impl<F: PrimeField> NullifierRegistry<F> for FileRegistry<F> {
+    fn get(&self, nullifier: &Nullifier<F>) -> Result<Option<StateHash<F>>, crate::Error> {
+        self.spends.get(nullifier)
+    }
+
+    fn insert(
+        &mut self,
+        nullifier: &Nullifier<F>,
+        spend: &StateHash<F>,
+    ) -> Result<(), crate::Error> {
+        match self.spends.get(nullifier)? {
+            Some(known) if known != *spend => Err(crate::Error::DoubleSpend),
+            Some(_) => Ok(()),
+            None => {
+                let mut record = vec![RECORD_SPEND];
+                nullifier.serialize_compressed(&mut record)?;
+                spend.serialize_compressed(&mut record)?;
+                self.append(&record)?;
+                self.spends.insert(nullifier, spend)
+            }
+        }
+    }
+
+    fn supply_link(
+        &self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+    ) -> Result<Option<SupplyHash<F>>, crate::Error> {
+        self.spends.supply_link(asset_hash, supply_in)
+    }
+
+    fn publish_supply(
+        &mut self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+        supply_out: &SupplyHash<F>,
+    ) -> Result<(), crate::Error> {
+        if !self
+            .spends
+            .extends_supply(asset_hash, supply_in, supply_out)?
+        {
+            return Ok(());
+        }
+        let mut record = vec![RECORD_SUPPLY];
+        asset_hash.serialize_compressed(&mut record)?;
+        supply_in.serialize_compressed(&mut record)?;
+        supply_out.serialize_compressed(&mut record)?;
+        self.append(&record)?;
+        self.spends
+            .publish_supply(asset_hash, supply_in, supply_out)
+    }
+}
+
+// registry shared by wallets of the same process
+impl<F: PrimeField, R: NullifierRegistry<F>> NullifierRegistry<F> for Rc<RefCell<R>> {
+    fn get(&self, nullifier: &Nullifier<F>) -> Result<Option<StateHash<F>>, crate::Error> {
+        self.borrow().get(nullifier)
+    }
+
+    fn insert(
+        &mut self,
+        nullifier: &Nullifier<F>,
+        spend: &StateHash<F>,
+    ) -> Result<(), crate::Error> {
+        self.borrow_mut().insert(nullifier, spend)
+    }
+
+    fn supply_link(
+        &self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+    ) -> Result<Option<SupplyHash<F>>, crate::Error> {
+        self.borrow().supply_link(asset_hash, supply_in)
+    }
+
+    fn publish_supply(
+        &mut self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+        supply_out: &SupplyHash<F>,
+    ) -> Result<(), crate::Error> {
+        self.borrow_mut()
+            .publish_supply(asset_hash, supply_in, supply_out)
+    }
+
+    fn get_batch(
+        &self,
+        nullifiers: &[Nullifier<F>],
+    ) -> Result<Vec<Option<StateHash<F>>>, crate::Error> {
+        self.borrow().get_batch(nullifiers)
+    }
+}
+
+// nullifiers spent along the history together with the output state of the
+// spending step. issue steps spend nothing
+#[allow(clippy::type_complexity)]
+pub(crate) fn spends<E: IVC>(
+    steps: &[IVCStep<E>],
+) -> Vec<(Nullifier<E::Field>, StateHash<E::Field>)> {
+    let mut spends = vec![];
+    for step in steps.iter() {
+        if step.nullifier != Nullifier::default() {
+            spends.push((step.nullifier, step.state));
+        }
+        if let Some(merged) = &step.merged {
+            spends.push((merged.nullifier, step.state));
+            spends.extend(self::spends(&merged.steps));
+        }
+    }
+    spends
+}
+
+// supply links of the issuances along the history, merged chains included
+pub(crate) fn issuances<E: IVC>(steps: &[IVCStep<E>]) -> Vec<Issuance<E::Field>> {
+    let mut issuances = vec![];
+    for step in steps.iter() {
+        issuances.extend(step.issuance);
+        if let Some(merged) = &step.merged {
+            issuances.extend(self::issuances(&merged.steps));
+        }
+    }
+    issuances
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FileRegistry, NullifierRegistry};
+    use crate::{AssetHash, Nullifier, StateHash, SupplyHash};
+    use ark_bn254::Fr;
+    use std::io::Write;
+
+    fn log_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("ivcnotes-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn spend(registry: &mut FileRegistry<Fr>, i: u64) {
+        let nullifier: Nullifier<Fr> = Fr::from(i).into();
+        let state: StateHash<Fr> = Fr::from(i + 100).into();
+        registry.insert(&nullifier, &state).unwrap();
+    }
+
+    #[test]
+    fn test_torn_tail() {
+        let path = log_path("torn");
+        let mut registry = FileRegistry::<Fr>::open(&path).unwrap();
+        spend(&mut registry, 1);
+        spend(&mut registry, 2);
+        drop(registry);
+        let len = std::fs::metadata(&path).unwrap().len();
+
+        // a crash while appending leaves part of a record
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&[40, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+        let mut registry = FileRegistry::<Fr>::open(&path).unwrap();
+        assert_eq!(registry.len(), 2);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+
+        // and records appended after reopening are replayed
+        spend(&mut registry, 3);
+        drop(registry);
+        let registry = FileRegistry::<Fr>::open(&path).unwrap();
+        assert_eq!(registry.len(), 3);
+        let state: StateHash<Fr> = Fr::from(103).into();
+        assert_eq!(registry.get(&Fr::from(3).into()).unwrap(), Some(state));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_record() {
+        let path = log_path("corrupt");
+        let mut registry = FileRegistry::<Fr>::open(&path).unwrap();
+        spend(&mut registry, 1);
+        spend(&mut registry, 2);
+        drop(registry);
+
+        // a flipped bit in the first record is not a torn tail
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[10] ^= 1;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(FileRegistry::<Fr>::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // spends and supply links are checked the same way after reopening
+    #[test]
+    fn test_replay_rules() {
+        let path = log_path("rules");
+        let nullifier: Nullifier<Fr> = Fr::from(1).into();
+        let state = |i: u64| -> StateHash<Fr> { Fr::from(i).into() };
+        let asset: AssetHash<Fr> = Fr::from(7).into();
+        let supply = |i: u64| -> SupplyHash<Fr> { Fr::from(i).into() };
+
+        let mut registry = FileRegistry::<Fr>::open(&path).unwrap();
+        registry.insert(&nullifier, &state(10)).unwrap();
+        registry
+            .publish_supply(&asset, &supply(0), &supply(1))
+            .unwrap();
+        registry
+            .publish_supply(&asset, &supply(1), &supply(2))
+            .unwrap();
+        drop(registry);
+        let len = std::fs::metadata(&path).unwrap().len();
+
+        let mut registry = FileRegistry::<Fr>::open(&path).unwrap();
+        assert_eq!(registry.get(&nullifier).unwrap(), Some(state(10)));
+        assert_eq!(
+            registry.supply_link(&asset, &supply(1)).unwrap(),
+            Some(supply(2))
+        );
+
+        // the same spend and link seen again are accepted and not logged twice
+        registry.insert(&nullifier, &state(10)).unwrap();
+        registry
+            .publish_supply(&asset, &supply(1), &supply(2))
+            .unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+
+        assert!(matches!(
+            registry.insert(&nullifier, &state(11)),
+            Err(crate::Error::DoubleSpend)
+        ));
+        // another link from a known commitment, a link off the head and a link
+        // back into the chain
+        for (supply_in, supply_out) in [(1, 3), (1, 1), (0, 3), (2, 0), (2, 1)] {
+            assert!(matches!(
+                registry.publish_supply(&asset, &supply(supply_in), &supply(supply_out)),
+                Err(crate::Error::SupplyConflict)
+            ));
+        }
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+
+        registry
+            .publish_supply(&asset, &supply(2), &supply(3))
+            .unwrap();
+        drop(registry);
+        let registry = FileRegistry::<Fr>::open(&path).unwrap();
+        assert_eq!(
+            registry.supply_link(&asset, &supply(2)).unwrap(),
+            Some(supply(3))
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
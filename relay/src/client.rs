@@ -0,0 +1,335 @@
+use crate::protocol::{read_frame, write_frame, Request, Response};
+use crate::{Endpoint, MailboxId, MailboxKey, Stream};
+use ark_ff::PrimeField;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ivcnotes::circuit::IVC;
+use ivcnotes::note::NoteHistory;
+use ivcnotes::wallet::registry::NullifierRegistry;
+use ivcnotes::wallet::CommReceiver;
+use ivcnotes::{Address, AssetHash, Nullifier, StateHash, SupplyHash};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+// keeps one connection to the relay that clones share, so it is cheap to clone
+// and can be handed to a wallet as its nullifier registry
+pub struct RelayClient {
+    endpoint: Endpoint,
+    connection: Arc<Mutex<Option<Stream>>>,
+}
+
+fn unexpected(response: Response) -> ivcnotes::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unexpected response {:?}", response),
+    )
+    .into()
+}
+
+impl RelayClient {
+    pub fn new(endpoint: &Endpoint) -> Self {
+        Self {
+            endpoint: endpoint.clone(),
+            connection: Default::default(),
+        }
+    }
+
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    // a kept connection may have been closed by the relay in the meantime, the
+    // request is sent once more over a new one then. every request is safe to
+    // repeat, a repeated push is at worst a history the receiver already holds
+    fn request(&self, request: &Request) -> Result<Response, ivcnotes::Error> {
+        let message = request.encode();
+        let mut connection = self.connection.lock().unwrap();
+        if let Some(stream) = connection.as_mut() {
+            if let Ok(frame) = Self::exchange(stream, &message) {
+                return Ok(Response::decode(&frame)?);
+            }
+        }
+        *connection = None;
+        let mut stream = self.endpoint.connect()?;
+        let frame = Self::exchange(&mut stream, &message)?;
+        *connection = Some(stream);
+        Ok(Response::decode(&frame)?)
+    }
+
+    fn exchange(stream: &mut Stream, message: &[u8]) -> io::Result<Vec<u8>> {
+        write_frame(&mut *stream, message)?;
+        read_frame(stream)?.ok_or(io::Error::from(io::ErrorKind::UnexpectedEof))
+    }
+
+    // queue note histories for `recipient` in the mailbox the recipient named
+    pub fn mailbox<F: PrimeField>(
+        &self,
+        recipient: &Address<F>,
+        mailbox: &MailboxId,
+    ) -> Mailbox<F> {
+        Mailbox {
+            client: self.clone(),
+            recipient: *recipient,
+            mailbox: *mailbox,
+        }
+    }
+
+    // page of the note histories queued in the mailbox of `key`, with their ids
+    // starting at `from`. histories stay queued until acknowledged. the page is
+    // empty once the mailbox is read to the end
+    #[allow(clippy::type_complexity)]
+    pub fn fetch<E: IVC>(
+        &self,
+        key: &MailboxKey,
+        from: u64,
+    ) -> Result<Vec<(u64, Result<NoteHistory<E>, ivcnotes::Error>)>, ivcnotes::Error> {
+        let request = Request::Pull {
+            key: key.to_bytes().to_vec(),
+            from,
+        };
+        match self.request(&request)? {
+            Response::Histories(histories) => Ok(histories
+                .iter()
+                .map(|(id, history)| {
+                    let history = NoteHistory::deserialize_compressed(&history[..]);
+                    (*id, history.map_err(Into::into))
+                })
+                .collect()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    // drop the histories with these ids from the mailbox of `key`
+    pub fn acknowledge(&self, key: &MailboxKey, ids: &[u64]) -> Result<(), ivcnotes::Error> {
+        let request = Request::Ack {
+            key: key.to_bytes().to_vec(),
+            ids: ids.to_vec(),
+        };
+        match self.request(&request)? {
+            Response::Ok => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    // hand note histories queued in the mailbox of `key` over to the receiver and
+    // return the number of accepted ones. accepted histories are acknowledged and
+    // so are those that don't decode or are rejected for good. histories rejected
+    // on an io error, e.g. an unreachable registry, stay queued for the next
+    // delivery
+    pub fn deliver<E: IVC>(
+        &self,
+        receiver: &mut impl CommReceiver<E>,
+        key: &MailboxKey,
+    ) -> Result<usize, ivcnotes::Error> {
+        let (mut from, mut accepted) = (0, 0);
+        loop {
+            let page = self.fetch::<E>(key, from)?;
+            let Some((last, _)) = page.last() else {
+                return Ok(accepted);
+            };
+            from = last + 1;
+            let mut done = vec![];
+            for (id, history) in page {
+                match history.and_then(|history| receiver.receive(&history)) {
+                    Ok(()) => {
+                        accepted += 1;
+                        done.push(id);
+                    }
+                    Err(ivcnotes::Error::Io(_)) => {}
+                    Err(_) => done.push(id),
+                }
+            }
+            if !done.is_empty() {
+                self.acknowledge(key, &done)?;
+            }
+        }
+    }
+}
+
+impl<F: PrimeField> NullifierRegistry<F> for RelayClient {
+    fn get(&self, nullifier: &Nullifier<F>) -> Result<Option<StateHash<F>>, ivcnotes::Error> {
+        let mut bytes = vec![];
+        nullifier.serialize_compressed(&mut bytes)?;
+        match self.request(&Request::Query { nullifier: bytes })? {
+            Response::Spent(spend) => Ok(Some(StateHash::deserialize_compressed(&spend[..])?)),
+            Response::Unspent => Ok(None),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn insert(
+        &mut self,
+        nullifier: &Nullifier<F>,
+        spend: &StateHash<F>,
+    ) -> Result<(), ivcnotes::Error> {
+        let (mut nullifier_bytes, mut spend_bytes) = (vec![], vec![]);
+        nullifier.serialize_compressed(&mut nullifier_bytes)?;
+        spend.serialize_compressed(&mut spend_bytes)?;
+        let request = Request::Publish {
+            nullifier: nullifier_bytes,
+            spend: spend_bytes,
+        };
+        match self.request(&request)? {
+            Response::Ok => Ok(()),
+            Response::Spent(_) => Err(ivcnotes::Error::DoubleSpend),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    // one round trip for all nullifiers
+    fn get_batch(
+        &self,
+        nullifiers: &[Nullifier<F>],
+    ) -> Result<Vec<Option<StateHash<F>>>, ivcnotes::Error> {
+        let nullifiers = nullifiers
+            .iter()
+            .map(|nullifier| {
+                let mut bytes = vec![];
+                nullifier.serialize_compressed(&mut bytes)?;
+                Ok(bytes)
+            })
+            .collect::<Result<Vec<_>, ivcnotes::Error>>()?;
+        let count = nullifiers.len();
+        match self.request(&Request::QueryBatch { nullifiers })? {
+            Response::Spends(spends) if spends.len() == count => spends
+                .iter()
+                .map(|spend| match spend {
+                    Some(spend) => Ok(Some(StateHash::deserialize_compressed(&spend[..])?)),
+                    None => Ok(None),
+                })
+                .collect(),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn supply_link(
+        &self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+    ) -> Result<Option<SupplyHash<F>>, ivcnotes::Error> {
+        let (mut asset, mut supply_in_bytes) = (vec![], vec![]);
+        asset_hash.serialize_compressed(&mut asset)?;
+        supply_in.serialize_compressed(&mut supply_in_bytes)?;
+        let request = Request::QuerySupply {
+            asset,
+            supply_in: supply_in_bytes,
+        };
+        match self.request(&request)? {
+            Response::Linked(supply_out) => {
+                Ok(Some(SupplyHash::deserialize_compressed(&supply_out[..])?))
+            }
+            Response::Unlinked => Ok(None),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn publish_supply(
+        &mut self,
+        asset_hash: &AssetHash<F>,
+        supply_in: &SupplyHash<F>,
+        supply_out: &SupplyHash<F>,
+    ) -> Result<(), ivcnotes::Error> {
+        let (mut asset, mut supply_in_bytes, mut supply_out_bytes) = (vec![], vec![], vec![]);
+        asset_hash.serialize_compressed(&mut asset)?;
+        supply_in.serialize_compressed(&mut supply_in_bytes)?;
+        supply_out.serialize_compressed(&mut supply_out_bytes)?;
+        let request = Request::PublishSupply {
+            asset,
+            supply_in: supply_in_bytes,
+            supply_out: supply_out_bytes,
+        };
+        match self.request(&request)? {
+            Response::Ok => Ok(()),
+            Response::SupplyConflict => Err(ivcnotes::Error::SupplyConflict),
+            response => Err(unexpected(response)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+// stand-in receiver that queues histories at the relay for a remote wallet
+pub struct Mailbox<F: PrimeField> {
+    client: RelayClient,
+    recipient: Address<F>,
+    mailbox: MailboxId,
+}
+
+impl<E: IVC> CommReceiver<E> for Mailbox<E::Field> {
+    fn receive(&mut self, history: &NoteHistory<E>) -> Result<(), ivcnotes::Error> {
+        let mut bytes = vec![];
+        history.serialize_compressed(&mut bytes)?;
+        let request = Request::Push {
+            mailbox: self.mailbox.0.to_vec(),
+            history: bytes,
+        };
+        match self.client.request(&request)? {
+            Response::Ok => Ok(()),
+            Response::MailboxFull => {
+                Err(io::Error::new(io::ErrorKind::WouldBlock, "mailbox is full").into())
+            }
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn address(&self) -> &Address<E::Field> {
+        &self.recipient
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RelayClient;
+    use crate::protocol::{read_frame, write_frame, Request};
+    use crate::{Endpoint, Relay};
+    use ark_bn254::Fr;
+    use ivcnotes::wallet::registry::NullifierRegistry;
+    use ivcnotes::{Nullifier, StateHash};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // relay that hangs up after every third request of a connection
+    fn flaky_relay() -> (Endpoint, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("tcp:{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        std::thread::spawn(move || {
+            let mut relay = Relay::new();
+            for mut stream in listener.incoming().map(Result::unwrap) {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                for _ in 0..3 {
+                    let Some(frame) = read_frame(&mut stream).unwrap() else {
+                        break;
+                    };
+                    let response = relay.handle(Request::decode(&frame).unwrap());
+                    write_frame(&mut stream, &response.encode()).unwrap();
+                }
+            }
+        });
+        (endpoint.parse().unwrap(), connections)
+    }
+
+    #[test]
+    fn test_batch_and_reconnect() {
+        let (endpoint, connections) = flaky_relay();
+        let mut client = RelayClient::new(&endpoint);
+        let nullifiers = (0..4u64)
+            .map(|i| Nullifier::from(Fr::from(i)))
+            .collect::<Vec<_>>();
+        let spend = StateHash::from(Fr::from(7));
+        client.insert(&nullifiers[1], &spend).unwrap();
+        client.insert(&nullifiers[3], &spend).unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        let spends = client.get_batch(&nullifiers).unwrap();
+        assert_eq!(spends, vec![None, Some(spend), None, Some(spend)]);
+
+        // the relay hung up, the request goes over a new connection
+        assert_eq!(client.get(&nullifiers[3]).unwrap(), Some(spend));
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        assert!(NullifierRegistry::<Fr>::get_batch(&client, &[])
+            .unwrap()
+            .is_empty());
+    }
+}
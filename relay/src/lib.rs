@@ -0,0 +1,160 @@
+use rand_core::CryptoRngCore;
+use sha2::{Digest, Sha256};
+use std::fmt::Display;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::str::FromStr;
+use zeroize::Zeroize;
+
+pub mod client;
+pub mod protocol;
+pub mod server;
+
+pub use client::{Mailbox, RelayClient};
+pub use server::Relay;
+
+// default endpoint of the relay, local only
+pub const DEFAULT_ENDPOINT: &str = "tcp:127.0.0.1:7070";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+// where the relay listens, written as `tcp:<ip>:<port>` or `unix:<path>`
+pub enum Endpoint {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Endpoint {
+    pub(crate) fn connect(&self) -> io::Result<Stream> {
+        match self {
+            Endpoint::Tcp(addr) => TcpStream::connect(addr).map(Stream::Tcp),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => UnixStream::connect(path).map(Stream::Unix),
+        }
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "bad endpoint {}, expected tcp:<ip>:<port> or unix:<path>",
+                    s
+                ),
+            )
+        };
+        match s.split_once(':') {
+            Some(("tcp", addr)) => addr.parse().map(Endpoint::Tcp).map_err(|_| invalid()),
+            #[cfg(unix)]
+            Some(("unix", path)) if !path.is_empty() => Ok(Endpoint::Unix(path.into())),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::Tcp(addr) => write!(f, "tcp:{}", addr),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+// secret of a mailbox at the relay. only its holder reads and acknowledges the
+// queued histories, so it is kept like the wallet secrets
+#[derive(Clone, PartialEq, Eq)]
+pub struct MailboxKey([u8; 32]);
+
+impl MailboxKey {
+    pub fn generate(rng: &mut impl CryptoRngCore) -> Self {
+        let mut key = [0u8; 32];
+        rng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+
+    // name of the mailbox, handed to senders along with the address
+    pub fn id(&self) -> MailboxId {
+        MailboxId::derive(&self.0)
+    }
+}
+
+impl std::fmt::Debug for MailboxKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MailboxKey(..)")
+    }
+}
+
+impl Drop for MailboxKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+// public name of a mailbox, senders queue histories under it
+pub struct MailboxId(pub [u8; 32]);
+
+impl MailboxId {
+    // hash of the key under a domain tag, the relay derives it from the key that
+    // comes with reads
+    pub(crate) fn derive(key: &[u8]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(b"ivcnotes relay mailbox");
+        hasher.update(key);
+        Self(hasher.finalize().into())
+    }
+}
+
+#[derive(Debug)]
+// connection to or from the relay
+pub(crate) enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
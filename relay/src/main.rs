@@ -0,0 +1,17 @@
+use relay::server::serve;
+use relay::{Endpoint, Relay, DEFAULT_ENDPOINT};
+
+// usage: relay [tcp:<ip>:<port> | unix:<path>]
+fn main() {
+    let endpoint = std::env::args()
+        .nth(1)
+        .unwrap_or(DEFAULT_ENDPOINT.to_string());
+    let result = endpoint.parse::<Endpoint>().and_then(|endpoint| {
+        println!("relay listening on {}", endpoint);
+        serve(&endpoint, Relay::new(), |err| eprintln!("relay: {}", err))
+    });
+    if let Err(err) = result {
+        eprintln!("relay: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,287 @@
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use std::io::{self, Read, Write};
+
+// upper bound of a single frame, a page of a busy mailbox is the largest message
+pub const MAX_FRAME: usize = 64 << 20;
+// largest queued history, leaves room for the framing of a page that holds it
+pub const MAX_HISTORY: usize = MAX_FRAME - (1 << 10);
+
+// messages are opaque bytes to the relay, so it serves wallets of any curve
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    // record that `nullifier` is spent by the step with output state `spend`
+    Publish {
+        nullifier: Vec<u8>,
+        spend: Vec<u8>,
+    },
+    // ask whether `nullifier` is spent
+    Query {
+        nullifier: Vec<u8>,
+    },
+    // queue a serialized note history in the mailbox with id `mailbox`
+    Push {
+        mailbox: Vec<u8>,
+        history: Vec<u8>,
+    },
+    // page of the queued note histories of the mailbox opened by `key`, with
+    // their ids starting at `from`. histories stay queued until they are
+    // acknowledged
+    Pull {
+        key: Vec<u8>,
+        from: u64,
+    },
+    // drop the histories with these ids from the mailbox opened by `key`
+    Ack {
+        key: Vec<u8>,
+        ids: Vec<u64>,
+    },
+    // extend the supply chain of `asset` from `supply_in` to `supply_out`
+    PublishSupply {
+        asset: Vec<u8>,
+        supply_in: Vec<u8>,
+        supply_out: Vec<u8>,
+    },
+    // ask for the commitment published after `supply_in` in the chain of `asset`
+    QuerySupply {
+        asset: Vec<u8>,
+        supply_in: Vec<u8>,
+    },
+    // ask whether each of `nullifiers` is spent
+    QueryBatch {
+        nullifiers: Vec<Vec<u8>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Ok,
+    // nullifier is spent by the step with this output state. answer to a query,
+    // or to a publish that conflicts with a known spend
+    Spent(Vec<u8>),
+    Unspent,
+    // queued note histories and their ids
+    Histories(Vec<(u64, Vec<u8>)>),
+    // supply commitment published after the queried one
+    Linked(Vec<u8>),
+    Unlinked,
+    // published supply link doesn't extend the chain
+    SupplyConflict,
+    // pushed mailbox id is not 32 bytes
+    BadMailbox,
+    // mailbox holds `server::MAX_QUEUED` histories
+    MailboxFull,
+    // pushed history is larger than `MAX_HISTORY`
+    TooLarge,
+    // output state of the spending step of each queried nullifier, `None` if unspent
+    Spends(Vec<Option<Vec<u8>>>),
+}
+
+const REQUEST_PUBLISH: u8 = 0;
+const REQUEST_QUERY: u8 = 1;
+const REQUEST_PUSH: u8 = 2;
+const REQUEST_PULL: u8 = 3;
+const REQUEST_PUBLISH_SUPPLY: u8 = 4;
+const REQUEST_QUERY_SUPPLY: u8 = 5;
+const REQUEST_ACK: u8 = 6;
+const REQUEST_QUERY_BATCH: u8 = 7;
+
+const RESPONSE_OK: u8 = 0;
+const RESPONSE_SPENT: u8 = 1;
+const RESPONSE_UNSPENT: u8 = 2;
+const RESPONSE_HISTORIES: u8 = 3;
+const RESPONSE_LINKED: u8 = 4;
+const RESPONSE_UNLINKED: u8 = 5;
+const RESPONSE_SUPPLY_CONFLICT: u8 = 6;
+const RESPONSE_BAD_MAILBOX: u8 = 7;
+const RESPONSE_MAILBOX_FULL: u8 = 8;
+const RESPONSE_TOO_LARGE: u8 = 9;
+const RESPONSE_SPENDS: u8 = 10;
+
+fn invalid_data(err: SerializationError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+fn encode(tag: u8, fields: &[&Vec<u8>]) -> Vec<u8> {
+    let mut bytes = vec![tag];
+    for field in fields {
+        field.serialize_compressed(&mut bytes).unwrap();
+    }
+    bytes
+}
+
+fn field(reader: &mut &[u8]) -> io::Result<Vec<u8>> {
+    Vec::<u8>::deserialize_compressed(reader).map_err(invalid_data)
+}
+
+// the whole body must be consumed, trailing bytes are a malformed message
+fn finish<T>(reader: &[u8], message: T) -> io::Result<T> {
+    reader
+        .is_empty()
+        .then_some(message)
+        .ok_or(io::Error::new(io::ErrorKind::InvalidData, "trailing bytes"))
+}
+
+fn split_tag(bytes: &[u8]) -> io::Result<(u8, &[u8])> {
+    bytes
+        .split_first()
+        .map(|(tag, reader)| (*tag, reader))
+        .ok_or(io::Error::new(io::ErrorKind::InvalidData, "empty message"))
+}
+
+fn unknown_tag(tag: u8) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("unknown tag {}", tag))
+}
+
+impl Request {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Request::Publish { nullifier, spend } => encode(REQUEST_PUBLISH, &[nullifier, spend]),
+            Request::Query { nullifier } => encode(REQUEST_QUERY, &[nullifier]),
+            Request::Push { mailbox, history } => encode(REQUEST_PUSH, &[mailbox, history]),
+            Request::Pull { key, from } => {
+                let mut bytes = encode(REQUEST_PULL, &[key]);
+                from.serialize_compressed(&mut bytes).unwrap();
+                bytes
+            }
+            Request::Ack { key, ids } => {
+                let mut bytes = encode(REQUEST_ACK, &[key]);
+                ids.serialize_compressed(&mut bytes).unwrap();
+                bytes
+            }
+            Request::PublishSupply {
+                asset,
+                supply_in,
+                supply_out,
+            } => encode(REQUEST_PUBLISH_SUPPLY, &[asset, supply_in, supply_out]),
+            Request::QuerySupply { asset, supply_in } => {
+                encode(REQUEST_QUERY_SUPPLY, &[asset, supply_in])
+            }
+            Request::QueryBatch { nullifiers } => {
+                let mut bytes = vec![REQUEST_QUERY_BATCH];
+                nullifiers.serialize_compressed(&mut bytes).unwrap();
+                bytes
+            }
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let (tag, mut reader) = split_tag(bytes)?;
+        let request = match tag {
+            REQUEST_PUBLISH => Request::Publish {
+                nullifier: field(&mut reader)?,
+                spend: field(&mut reader)?,
+            },
+            REQUEST_QUERY => Request::Query {
+                nullifier: field(&mut reader)?,
+            },
+            REQUEST_PUSH => Request::Push {
+                mailbox: field(&mut reader)?,
+                history: field(&mut reader)?,
+            },
+            REQUEST_PULL => Request::Pull {
+                key: field(&mut reader)?,
+                from: u64::deserialize_compressed(&mut reader).map_err(invalid_data)?,
+            },
+            REQUEST_ACK => Request::Ack {
+                key: field(&mut reader)?,
+                ids: Vec::<u64>::deserialize_compressed(&mut reader).map_err(invalid_data)?,
+            },
+            REQUEST_PUBLISH_SUPPLY => Request::PublishSupply {
+                asset: field(&mut reader)?,
+                supply_in: field(&mut reader)?,
+                supply_out: field(&mut reader)?,
+            },
+            REQUEST_QUERY_SUPPLY => Request::QuerySupply {
+                asset: field(&mut reader)?,
+                supply_in: field(&mut reader)?,
+            },
+            REQUEST_QUERY_BATCH => Request::QueryBatch {
+                nullifiers: Vec::<Vec<u8>>::deserialize_compressed(&mut reader)
+                    .map_err(invalid_data)?,
+            },
+            _ => return Err(unknown_tag(tag)),
+        };
+        finish(reader, request)
+    }
+}
+
+impl Response {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Response::Ok => encode(RESPONSE_OK, &[]),
+            Response::Spent(spend) => encode(RESPONSE_SPENT, &[spend]),
+            Response::Unspent => encode(RESPONSE_UNSPENT, &[]),
+            Response::Histories(histories) => {
+                let mut bytes = vec![RESPONSE_HISTORIES];
+                histories.serialize_compressed(&mut bytes).unwrap();
+                bytes
+            }
+            Response::Linked(supply_out) => encode(RESPONSE_LINKED, &[supply_out]),
+            Response::Unlinked => encode(RESPONSE_UNLINKED, &[]),
+            Response::SupplyConflict => encode(RESPONSE_SUPPLY_CONFLICT, &[]),
+            Response::BadMailbox => encode(RESPONSE_BAD_MAILBOX, &[]),
+            Response::MailboxFull => encode(RESPONSE_MAILBOX_FULL, &[]),
+            Response::TooLarge => encode(RESPONSE_TOO_LARGE, &[]),
+            Response::Spends(spends) => {
+                let mut bytes = vec![RESPONSE_SPENDS];
+                spends.serialize_compressed(&mut bytes).unwrap();
+                bytes
+            }
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let (tag, mut reader) = split_tag(bytes)?;
+        let response = match tag {
+            RESPONSE_OK => Response::Ok,
+            RESPONSE_SPENT => Response::Spent(field(&mut reader)?),
+            RESPONSE_UNSPENT => Response::Unspent,
+            RESPONSE_HISTORIES => Response::Histories(
+                Vec::<(u64, Vec<u8>)>::deserialize_compressed(&mut reader).map_err(invalid_data)?,
+            ),
+            RESPONSE_LINKED => Response::Linked(field(&mut reader)?),
+            RESPONSE_UNLINKED => Response::Unlinked,
+            RESPONSE_SUPPLY_CONFLICT => Response::SupplyConflict,
+            RESPONSE_BAD_MAILBOX => Response::BadMailbox,
+            RESPONSE_MAILBOX_FULL => Response::MailboxFull,
+            RESPONSE_TOO_LARGE => Response::TooLarge,
+            RESPONSE_SPENDS => Response::Spends(
+                Vec::<Option<Vec<u8>>>::deserialize_compressed(&mut reader)
+                    .map_err(invalid_data)?,
+            ),
+            _ => return Err(unknown_tag(tag)),
+        };
+        finish(reader, response)
+    }
+}
+
+// frames are a little endian u32 length followed by the message
+pub fn write_frame(mut writer: impl Write, message: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(message.len())
+        .ok()
+        .filter(|len| *len as usize <= MAX_FRAME)
+        .ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "frame too large",
+        ))?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(message)?;
+    writer.flush()
+}
+
+// `None` if the peer closed the connection between frames
+pub fn read_frame(mut reader: impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    (len <= MAX_FRAME).then_some(()).ok_or(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "frame too large",
+    ))?;
+    let mut message = vec![0u8; len];
+    reader.read_exact(&mut message)?;
+    Ok(Some(message))
+}
@@ -0,0 +1,289 @@
+use crate::protocol::{read_frame, write_frame, Request, Response, MAX_HISTORY};
+use crate::{Endpoint, MailboxId, Stream};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io;
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// histories a mailbox holds before pushes are refused. anyone who knows the id
+// can push, the owner drains junk by acknowledging it
+pub const MAX_QUEUED: usize = 1024;
+// bytes of histories in a page, at least one history is always returned
+const MAX_PAGE: usize = MAX_HISTORY;
+
+#[derive(Debug)]
+// failure the relay keeps serving through, reported to the caller of `serve`
+pub enum ServeError {
+    // accepting a connection failed, e.g. out of file descriptors
+    Accept(io::Error),
+    // connection is dropped after a malformed request or an io failure
+    Connection(io::Error),
+}
+
+impl Display for ServeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServeError::Accept(err) => write!(f, "accept failed: {}", err),
+            ServeError::Connection(err) => write!(f, "connection closed: {}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+// state of the relay, kept in memory only
+pub struct Relay {
+    // nullifier to the output state of the step that spent it
+    spends: BTreeMap<Vec<u8>, Vec<u8>>,
+    // queued note histories per mailbox id, keyed by their ids
+    mailboxes: BTreeMap<MailboxId, BTreeMap<u64, Vec<u8>>>,
+    // id of the next queued history
+    next_id: u64,
+    // supply chain links keyed by the asset and the opened commitment
+    links: BTreeMap<(Vec<u8>, Vec<u8>), Vec<u8>>,
+    // latest published supply commitment of each asset
+    heads: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Relay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::Publish { nullifier, spend } => match self.spends.get(&nullifier) {
+                Some(known) if *known != spend => Response::Spent(known.clone()),
+                Some(_) => Response::Ok,
+                None => {
+                    self.spends.insert(nullifier, spend);
+                    Response::Ok
+                }
+            },
+            Request::Query { nullifier } => match self.spends.get(&nullifier) {
+                Some(spend) => Response::Spent(spend.clone()),
+                None => Response::Unspent,
+            },
+            Request::QueryBatch { nullifiers } => Response::Spends(
+                nullifiers
+                    .iter()
+                    .map(|nullifier| self.spends.get(nullifier).cloned())
+                    .collect(),
+            ),
+            Request::Push { mailbox, history } => match <[u8; 32]>::try_from(mailbox) {
+                Ok(_) if history.len() > MAX_HISTORY => Response::TooLarge,
+                Ok(mailbox) => {
+                    let mailbox = self.mailboxes.entry(MailboxId(mailbox)).or_default();
+                    if mailbox.len() >= MAX_QUEUED {
+                        return Response::MailboxFull;
+                    }
+                    mailbox.insert(self.next_id, history);
+                    self.next_id += 1;
+                    Response::Ok
+                }
+                Err(_) => Response::BadMailbox,
+            },
+            // reads need the key of the mailbox, the id alone only lets histories in
+            Request::Pull { key, from } => {
+                let mut page = vec![];
+                let mut size = 0;
+                if let Some(mailbox) = self.mailboxes.get(&MailboxId::derive(&key)) {
+                    for (id, history) in mailbox.range(from..) {
+                        size += history.len();
+                        if !page.is_empty() && size > MAX_PAGE {
+                            break;
+                        }
+                        page.push((*id, history.clone()));
+                    }
+                }
+                Response::Histories(page)
+            }
+            Request::Ack { key, ids } => {
+                let id = MailboxId::derive(&key);
+                if let Some(mailbox) = self.mailboxes.get_mut(&id) {
+                    ids.iter().for_each(|id| {
+                        mailbox.remove(id);
+                    });
+                    if mailbox.is_empty() {
+                        self.mailboxes.remove(&id);
+                    }
+                }
+                Response::Ok
+            }
+            Request::PublishSupply {
+                asset,
+                supply_in,
+                supply_out,
+            } => self.publish_supply(asset, supply_in, supply_out),
+            Request::QuerySupply { asset, supply_in } => {
+                match self.links.get(&(asset, supply_in)) {
+                    Some(supply_out) => Response::Linked(supply_out.clone()),
+                    None => Response::Unlinked,
+                }
+            }
+        }
+    }
+
+    // same rules as `MemoryRegistry`. commitments are opaque, but a serialized zero
+    // field element is all zero bytes in any field
+    fn publish_supply(
+        &mut self,
+        asset: Vec<u8>,
+        supply_in: Vec<u8>,
+        supply_out: Vec<u8>,
+    ) -> Response {
+        let is_zero = |bytes: &[u8]| bytes.iter().all(|b| *b == 0);
+        let key = (asset, supply_in);
+        match self.links.get(&key) {
+            Some(known) if *known == supply_out => Response::Ok,
+            Some(_) => Response::SupplyConflict,
+            None => {
+                let (asset, supply_in) = key;
+                let extends = match self.heads.get(&asset) {
+                    Some(head) => *head == supply_in,
+                    None => is_zero(&supply_in),
+                };
+                let loops = self
+                    .links
+                    .contains_key(&(asset.clone(), supply_out.clone()));
+                if !extends || loops || is_zero(&supply_out) {
+                    return Response::SupplyConflict;
+                }
+                self.heads.insert(asset.clone(), supply_out.clone());
+                self.links.insert((asset, supply_in), supply_out);
+                Response::Ok
+            }
+        }
+    }
+}
+
+// answer requests of a single connection until the peer hangs up
+fn serve_connection(mut stream: Stream, relay: &Mutex<Relay>) -> io::Result<()> {
+    while let Some(frame) = read_frame(&mut stream)? {
+        let request = Request::decode(&frame)?;
+        let response = relay.lock().unwrap().handle(request);
+        write_frame(&mut stream, &response.encode())?;
+    }
+    Ok(())
+}
+
+fn spawn_connection(stream: Stream, relay: &Arc<Mutex<Relay>>, report: fn(ServeError)) {
+    let relay = relay.clone();
+    thread::spawn(move || {
+        if let Err(err) = serve_connection(stream, &relay) {
+            report(ServeError::Connection(err));
+        }
+    });
+}
+
+// a failed accept, e.g. out of file descriptors, only costs that connection. the
+// pause keeps a persistent failure from spinning
+fn accept_connections<S>(
+    incoming: impl Iterator<Item = io::Result<S>>,
+    stream: fn(S) -> Stream,
+    relay: &Arc<Mutex<Relay>>,
+    report: fn(ServeError),
+) {
+    for accepted in incoming {
+        match accepted {
+            Ok(accepted) => spawn_connection(stream(accepted), relay, report),
+            Err(err) => {
+                report(ServeError::Accept(err));
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+// accept connections forever, one thread per connection. failures the relay
+// survives are handed to `report`. the relay has no access control beyond mailbox
+// keys, so it only listens on loopback addresses and unix sockets
+pub fn serve(endpoint: &Endpoint, relay: Relay, report: fn(ServeError)) -> io::Result<()> {
+    let relay = Arc::new(Mutex::new(relay));
+    match endpoint {
+        Endpoint::Tcp(addr) => {
+            addr.ip().is_loopback().then_some(()).ok_or(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("refusing to listen on non-loopback address {}", addr),
+            ))?;
+            let listener = TcpListener::bind(addr)?;
+            accept_connections(listener.incoming(), Stream::Tcp, &relay, report);
+        }
+        #[cfg(unix)]
+        Endpoint::Unix(path) => {
+            // socket file of a previous run would make bind fail. anything else at
+            // the path is left alone
+            match std::fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} exists and is not a socket", path.display()),
+                    ))
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+            let listener = UnixListener::bind(path)?;
+            accept_connections(listener.incoming(), Stream::Unix, &relay, report);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{serve, Relay, ServeError};
+    use crate::protocol::{read_frame, write_frame, Request, Response};
+    use crate::Endpoint;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    fn report(err: ServeError) {
+        if let ServeError::Connection(_) = err {
+            DROPPED.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_serve_reports() {
+        let public: Endpoint = "tcp:0.0.0.0:0".parse().unwrap();
+        assert!(serve(&public, Relay::new(), report).is_err());
+
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let endpoint: Endpoint = format!("tcp:{}", addr).parse().unwrap();
+        let served = endpoint.clone();
+        std::thread::spawn(move || serve(&served, Relay::new(), report));
+        let mut stream = loop {
+            match endpoint.connect() {
+                Ok(stream) => break stream,
+                Err(_) => std::thread::sleep(Duration::from_millis(10)),
+            }
+        };
+
+        let query = Request::Query {
+            nullifier: vec![1; 32],
+        };
+        write_frame(&mut stream, &query.encode()).unwrap();
+        let response = read_frame(&mut stream).unwrap().unwrap();
+        assert_eq!(Response::decode(&response).unwrap(), Response::Unspent);
+
+        // a malformed request drops the connection and is reported
+        write_frame(&mut stream, &[0xff]).unwrap();
+        assert!(matches!(read_frame(&mut stream), Ok(None) | Err(_)));
+        while DROPPED.load(Ordering::SeqCst) == 0 {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}